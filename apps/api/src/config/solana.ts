@@ -17,6 +17,25 @@ export const PROGRAM_IDS = {
     : null,
 };
 
+export function getSquadCounterAddress(authorityPubkey: PublicKey): [PublicKey, number] {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from('squad_counter'), authorityPubkey.toBuffer()],
+    PROGRAM_IDS.squad!
+  );
+}
+
+export function getSquadAddress(
+  authorityPubkey: PublicKey,
+  squadId: bigint
+): [PublicKey, number] {
+  const squadIdBuffer = Buffer.alloc(8);
+  squadIdBuffer.writeBigUInt64LE(squadId);
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from('squad'), authorityPubkey.toBuffer(), squadIdBuffer],
+    PROGRAM_IDS.squad!
+  );
+}
+
 export function getSquadVaultAddress(squadPubkey: PublicKey): [PublicKey, number] {
   return PublicKey.findProgramAddressSync(
     [Buffer.from('vault'), squadPubkey.toBuffer()],
@@ -0,0 +1,450 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+
+declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnV");
+
+/// Rally Recurring — Scheduled recurring payments on Solana.
+/// Payer prefunds a vault; anyone can crank each instalment to the recipient
+/// once it falls due.
+#[program]
+pub mod rally_recurring {
+    use super::*;
+
+    /// Create a recurring payment of `amount` every `interval` seconds,
+    /// starting at `first_due`, for `count` instalments.
+    /// All instalments are prefunded into a vault PDA, plus the vault's
+    /// rent-exempt minimum so payouts never leave it below rent exemption.
+    pub fn create_recurring_payment(
+        ctx: Context<CreateRecurringPayment>,
+        payment_id: u64,
+        amount: u64,
+        interval: i64,
+        first_due: i64,
+        count: u32,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(amount > 0, RecurringError::InvalidAmount);
+        require!(interval > 0, RecurringError::InvalidInterval);
+        require!(count > 0, RecurringError::InvalidCount);
+        require!(first_due >= now - 60, RecurringError::InvalidStartTime); // Allow 60s grace
+
+        let total_deposit = amount.checked_mul(count as u64)
+            .ok_or(RecurringError::Overflow)?;
+
+        // Prefund every instalment into the vault, plus its rent reserve
+        let rent_reserve = vault_rent_reserve()?;
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.payer.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                },
+            ),
+            total_deposit.checked_add(rent_reserve)
+                .ok_or(RecurringError::Overflow)?,
+        )?;
+
+        let payment = &mut ctx.accounts.recurring_payment;
+        payment.payer = ctx.accounts.payer.key();
+        payment.recipient = ctx.accounts.recipient.key();
+        payment.payment_id = payment_id;
+        payment.amount = amount;
+        payment.interval = interval;
+        payment.next_due = first_due;
+        payment.count = count;
+        payment.payments_made = 0;
+        payment.total_deposited = total_deposit;
+        payment.total_paid = 0;
+        payment.vault_bump = ctx.bumps.vault;
+        payment.created_at = now;
+
+        emit!(RecurringPaymentCreated {
+            recurring_payment: payment.key(),
+            payer: payment.payer,
+            recipient: payment.recipient,
+            payment_id,
+            amount,
+            interval,
+            first_due,
+            count,
+            total_deposited: total_deposit,
+        });
+        msg!(
+            "Recurring payment created: {} x {} lamports from {} to {}, every {} sec",
+            count,
+            amount,
+            payment.payer,
+            payment.recipient,
+            interval
+        );
+        Ok(())
+    }
+
+    /// Pay every instalment that has fallen due, catching up on any missed
+    /// intervals. Permissionless: anyone can crank.
+    pub fn crank_payment(ctx: Context<CrankPayment>) -> Result<()> {
+        let payment = &ctx.accounts.recurring_payment;
+        let now = Clock::get()?.unix_timestamp;
+
+        let due = payment.instalments_due(now);
+        require!(due > 0, RecurringError::NothingDue);
+
+        let pay_amount = payment.amount.checked_mul(due as u64)
+            .ok_or(RecurringError::Overflow)?
+            .min(ctx.accounts.vault.lamports().saturating_sub(vault_rent_reserve()?));
+
+        transfer_from_vault(
+            payment,
+            &ctx.accounts.vault,
+            ctx.accounts.recipient.to_account_info(),
+            &ctx.accounts.system_program,
+            pay_amount,
+        )?;
+
+        let payment = &mut ctx.accounts.recurring_payment;
+        payment.record_paid(due, pay_amount)?;
+
+        emit!(RecurringPaymentCranked {
+            recurring_payment: payment.key(),
+            recipient: payment.recipient,
+            cranker: ctx.accounts.cranker.key(),
+            instalments: due,
+            amount: pay_amount,
+            payments_made: payment.payments_made,
+            next_due: payment.next_due,
+        });
+        msg!(
+            "Paid {} instalment(s), {} lamports. {}/{} complete",
+            due,
+            pay_amount,
+            payment.payments_made,
+            payment.count
+        );
+        Ok(())
+    }
+
+    /// Payer cancels the recurring payment. Instalments already due are paid
+    /// to the recipient first; unused prefunding and the vault's rent reserve
+    /// are refunded to the payer and the payment account is closed.
+    pub fn cancel_recurring_payment(ctx: Context<CancelRecurringPayment>) -> Result<()> {
+        let payment = &ctx.accounts.recurring_payment;
+        let now = Clock::get()?.unix_timestamp;
+
+        // Settle anything the recipient is already owed
+        let due = payment.instalments_due(now);
+        let mut remaining = ctx.accounts.vault.lamports();
+        let owed = payment.amount.checked_mul(due as u64)
+            .ok_or(RecurringError::Overflow)?
+            .min(remaining.saturating_sub(vault_rent_reserve()?));
+        if owed > 0 {
+            transfer_from_vault(
+                payment,
+                &ctx.accounts.vault,
+                ctx.accounts.recipient.to_account_info(),
+                &ctx.accounts.system_program,
+                owed,
+            )?;
+            remaining -= owed;
+        }
+
+        // Refund unused prefunding
+        if remaining > 0 {
+            transfer_from_vault(
+                payment,
+                &ctx.accounts.vault,
+                ctx.accounts.payer.to_account_info(),
+                &ctx.accounts.system_program,
+                remaining,
+            )?;
+        }
+
+        emit!(RecurringPaymentCancelled {
+            recurring_payment: payment.key(),
+            payer: payment.payer,
+            paid_to_recipient: owed,
+            refunded: remaining,
+        });
+        msg!(
+            "Recurring payment cancelled. Paid {} to recipient, refunded {} to payer",
+            owed,
+            remaining
+        );
+        Ok(())
+    }
+}
+
+/// Lamports kept in the vault on top of the instalments, so it stays
+/// rent-exempt between payouts. Only released when the payment is cancelled.
+fn vault_rent_reserve() -> Result<u64> {
+    Ok(Rent::get()?.minimum_balance(0))
+}
+
+/// Move lamports out of the vault, signing as the vault PDA.
+fn transfer_from_vault<'info>(
+    payment: &Account<'info, RecurringPayment>,
+    vault: &SystemAccount<'info>,
+    to: AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    amount: u64,
+) -> Result<()> {
+    let payment_key = payment.key();
+    let vault_seeds = &[b"recurring_vault", payment_key.as_ref(), &[payment.vault_bump]];
+    let signer_seeds = &[&vault_seeds[..]];
+
+    system_program::transfer(
+        CpiContext::new_with_signer(
+            system_program.to_account_info(),
+            system_program::Transfer {
+                from: vault.to_account_info(),
+                to,
+            },
+            signer_seeds,
+        ),
+        amount,
+    )
+}
+
+// === Accounts ===
+
+#[derive(Accounts)]
+#[instruction(payment_id: u64)]
+pub struct CreateRecurringPayment<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = RecurringPayment::SPACE,
+        seeds = [b"recurring", payer.key().as_ref(), payment_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub recurring_payment: Account<'info, RecurringPayment>,
+
+    /// CHECK: PDA vault that holds the prefunded instalments
+    #[account(
+        mut,
+        seeds = [b"recurring_vault", recurring_payment.key().as_ref()],
+        bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: Recipient of the instalments
+    pub recipient: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CrankPayment<'info> {
+    #[account(mut, has_one = recipient @ RecurringError::Unauthorized)]
+    pub recurring_payment: Account<'info, RecurringPayment>,
+
+    /// CHECK: PDA vault
+    #[account(
+        mut,
+        seeds = [b"recurring_vault", recurring_payment.key().as_ref()],
+        bump = recurring_payment.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: Recipient, validated against the payment
+    #[account(mut)]
+    pub recipient: UncheckedAccount<'info>,
+
+    pub cranker: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelRecurringPayment<'info> {
+    #[account(
+        mut,
+        has_one = payer @ RecurringError::Unauthorized,
+        has_one = recipient @ RecurringError::Unauthorized,
+        close = payer
+    )]
+    pub recurring_payment: Account<'info, RecurringPayment>,
+
+    /// CHECK: PDA vault
+    #[account(
+        mut,
+        seeds = [b"recurring_vault", recurring_payment.key().as_ref()],
+        bump = recurring_payment.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Recipient gets any instalments already due on cancel
+    #[account(mut)]
+    pub recipient: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// === State ===
+
+#[account]
+pub struct RecurringPayment {
+    pub payer: Pubkey,          // 32
+    pub recipient: Pubkey,      // 32
+    pub payment_id: u64,        // 8
+    pub amount: u64,            // 8, per instalment
+    pub interval: i64,          // 8, seconds between instalments
+    pub next_due: i64,          // 8
+    pub count: u32,             // 4, total instalments
+    pub payments_made: u32,     // 4
+    pub total_deposited: u64,   // 8
+    pub total_paid: u64,        // 8
+    pub vault_bump: u8,         // 1
+    pub created_at: i64,        // 8
+}
+
+impl RecurringPayment {
+    pub const SPACE: usize = 8 + 32 + 32 + 8 + 8 + 8 + 8 + 4 + 4 + 8 + 8 + 1 + 8 + 32; // + padding
+
+    /// Instalments due at `now` and not yet paid, including missed intervals.
+    pub fn instalments_due(&self, now: i64) -> u32 {
+        let remaining = self.count - self.payments_made;
+        if remaining == 0 || now < self.next_due {
+            return 0;
+        }
+        let elapsed_intervals = (now - self.next_due) / self.interval + 1;
+        elapsed_intervals.min(remaining as i64) as u32
+    }
+
+    /// Advance the schedule past `instalments` paid instalments.
+    fn record_paid(&mut self, instalments: u32, amount: u64) -> Result<()> {
+        self.payments_made += instalments;
+        self.total_paid = self.total_paid.checked_add(amount)
+            .ok_or(RecurringError::Overflow)?;
+        self.next_due = (instalments as i64)
+            .checked_mul(self.interval)
+            .and_then(|advance| self.next_due.checked_add(advance))
+            .ok_or(RecurringError::Overflow)?;
+        Ok(())
+    }
+}
+
+// === Events ===
+
+#[event]
+pub struct RecurringPaymentCreated {
+    pub recurring_payment: Pubkey,
+    pub payer: Pubkey,
+    pub recipient: Pubkey,
+    pub payment_id: u64,
+    pub amount: u64,
+    pub interval: i64,
+    pub first_due: i64,
+    pub count: u32,
+    pub total_deposited: u64,
+}
+
+#[event]
+pub struct RecurringPaymentCranked {
+    pub recurring_payment: Pubkey,
+    pub recipient: Pubkey,
+    pub cranker: Pubkey,
+    pub instalments: u32,
+    pub amount: u64,
+    pub payments_made: u32,
+    pub next_due: i64,
+}
+
+#[event]
+pub struct RecurringPaymentCancelled {
+    pub recurring_payment: Pubkey,
+    pub payer: Pubkey,
+    pub paid_to_recipient: u64,
+    pub refunded: u64,
+}
+
+// === Errors ===
+
+#[error_code]
+pub enum RecurringError {
+    #[msg("First payment cannot be in the past")]
+    InvalidStartTime,
+    #[msg("Interval must be greater than 0")]
+    InvalidInterval,
+    #[msg("Amount must be greater than 0")]
+    InvalidAmount,
+    #[msg("Instalment count must be greater than 0")]
+    InvalidCount,
+    #[msg("Unauthorized: only payer/recipient can perform this action")]
+    Unauthorized,
+    #[msg("No instalment is due yet")]
+    NothingDue,
+    #[msg("Arithmetic overflow")]
+    Overflow,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payment(next_due: i64, interval: i64, count: u32, payments_made: u32) -> RecurringPayment {
+        RecurringPayment {
+            payer: Pubkey::new_unique(),
+            recipient: Pubkey::new_unique(),
+            payment_id: 0,
+            amount: 100,
+            interval,
+            next_due,
+            count,
+            payments_made,
+            total_deposited: 100 * count as u64,
+            total_paid: 100 * payments_made as u64,
+            vault_bump: 255,
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn nothing_due_before_next_due() {
+        let payment = payment(1_000, 60, 5, 0);
+        assert_eq!(payment.instalments_due(0), 0);
+        assert_eq!(payment.instalments_due(999), 0);
+    }
+
+    #[test]
+    fn one_instalment_due_per_elapsed_interval() {
+        let payment = payment(1_000, 60, 5, 0);
+        assert_eq!(payment.instalments_due(1_000), 1);
+        assert_eq!(payment.instalments_due(1_059), 1);
+        assert_eq!(payment.instalments_due(1_060), 2);
+        assert_eq!(payment.instalments_due(1_180), 4);
+    }
+
+    #[test]
+    fn missed_intervals_are_capped_by_remaining_count() {
+        let payment = payment(1_000, 60, 5, 3);
+        assert_eq!(payment.instalments_due(1_000), 1);
+        assert_eq!(payment.instalments_due(100_000), 2);
+    }
+
+    #[test]
+    fn nothing_due_once_complete() {
+        let payment = payment(1_000, 60, 5, 5);
+        assert_eq!(payment.instalments_due(100_000), 0);
+    }
+
+    #[test]
+    fn record_paid_advances_schedule() {
+        let mut payment = payment(1_000, 60, 5, 0);
+        let due = payment.instalments_due(1_130);
+        assert_eq!(due, 3);
+        payment.record_paid(due, 300).unwrap();
+        assert_eq!(payment.payments_made, 3);
+        assert_eq!(payment.next_due, 1_180);
+        assert_eq!(payment.total_paid, 300);
+        assert_eq!(payment.instalments_due(1_130), 0);
+        assert_eq!(payment.instalments_due(1_180), 1);
+    }
+}
@@ -1,5 +1,11 @@
+#![allow(clippy::too_many_arguments)]
+
 use anchor_lang::prelude::*;
 use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+use anchor_spl::token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked};
+use rally_stream::program::RallyStream;
+use rally_stream::{PaymentStream, SplitShare, StreamRate};
 
 declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
 
@@ -10,78 +16,507 @@ pub mod rally_squad {
     use super::*;
 
     /// Create a new squad with a shared vault.
+    /// The authority is the only member, as `Role::Admin`; everyone else
+    /// joins by accepting an invite.
+    /// The squad id is allocated from the authority's `SquadCounter`, so one
+    /// wallet can create any number of squads.
     pub fn initialize_squad(
         ctx: Context<InitializeSquad>,
         name: String,
-        members: Vec<Pubkey>,
         spend_threshold: u64,
     ) -> Result<()> {
         require!(name.len() <= 32, RallySquadError::NameTooLong);
-        require!(members.len() <= 10, RallySquadError::TooManyMembers);
         require!(spend_threshold > 0, RallySquadError::InvalidThreshold);
 
         let squad = &mut ctx.accounts.squad;
-        squad.authority = ctx.accounts.authority.key();
-        squad.name = name;
-        squad.members = members;
-        squad.vault_bump = ctx.bumps.vault;
-        squad.spend_threshold = spend_threshold;
-        squad.total_deposited = 0;
-        squad.created_at = Clock::get()?.unix_timestamp;
-
-        // Ensure authority is in members list
-        if !squad.members.contains(&squad.authority) {
-            squad.members.push(squad.authority);
+        squad.init(
+            ctx.accounts.authority.key(),
+            name,
+            spend_threshold,
+            ctx.bumps.vault,
+            None,
+            ctx.accounts.squad_counter.next_squad_id,
+            ctx.bumps.squad,
+        )?;
+        ctx.accounts
+            .squad_counter
+            .advance(ctx.accounts.authority.key(), ctx.bumps.squad_counter)?;
+
+        emit!(SquadCreated {
+            squad: squad.key(),
+            authority: squad.authority,
+            squad_id: squad.squad_id,
+            name: squad.name.clone(),
+            spend_threshold,
+            mint: None,
+        });
+        msg!("Squad '{}' created by {}", squad.name, squad.authority);
+        Ok(())
+    }
+
+    /// Create a new squad whose vault holds an SPL token (Token or Token-2022).
+    /// The vault token account is owned by the vault PDA and spend_threshold
+    /// is in the mint's base units.
+    pub fn initialize_token_squad(
+        ctx: Context<InitializeTokenSquad>,
+        name: String,
+        spend_threshold: u64,
+    ) -> Result<()> {
+        require!(name.len() <= 32, RallySquadError::NameTooLong);
+        require!(spend_threshold > 0, RallySquadError::InvalidThreshold);
+
+        let squad = &mut ctx.accounts.squad;
+        squad.init(
+            ctx.accounts.authority.key(),
+            name,
+            spend_threshold,
+            ctx.bumps.vault,
+            Some(ctx.accounts.mint.key()),
+            ctx.accounts.squad_counter.next_squad_id,
+            ctx.bumps.squad,
+        )?;
+        ctx.accounts
+            .squad_counter
+            .advance(ctx.accounts.authority.key(), ctx.bumps.squad_counter)?;
+
+        emit!(SquadCreated {
+            squad: squad.key(),
+            authority: squad.authority,
+            squad_id: squad.squad_id,
+            name: squad.name.clone(),
+            spend_threshold,
+            mint: squad.mint,
+        });
+        msg!(
+            "Token squad '{}' created by {}, mint {}",
+            squad.name,
+            squad.authority,
+            ctx.accounts.mint.key()
+        );
+        Ok(())
+    }
+
+    /// Invite `invitee` to join the squad with the given role, optionally
+    /// expiring at `expires_at`. Only admins can do this; the invitee joins
+    /// by calling `accept_invite`.
+    pub fn create_invite(
+        ctx: Context<CreateInvite>,
+        invitee: Pubkey,
+        role: Role,
+        expires_at: Option<i64>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            ctx.accounts.squad.role_of(&invitee).is_none(),
+            RallySquadError::AlreadyMember
+        );
+        if let Some(expires_at) = expires_at {
+            require!(expires_at > now, RallySquadError::InviteExpired);
         }
 
-        msg!("Squad '{}' created with {} members", squad.name, squad.members.len());
+        let invite = &mut ctx.accounts.invite;
+        invite.squad = ctx.accounts.squad.key();
+        invite.invitee = invitee;
+        invite.inviter = ctx.accounts.admin.key();
+        invite.role = role;
+        invite.expires_at = expires_at;
+        invite.created_at = now;
+        invite.bump = ctx.bumps.invite;
+
+        emit!(InviteCreated {
+            squad: invite.squad,
+            invite: invite.key(),
+            invitee,
+            inviter: invite.inviter,
+            role,
+            expires_at,
+        });
+        msg!("Invited {} to squad as {:?}", invitee, role);
         Ok(())
     }
 
-    /// Add a member to the squad. Only the authority can do this.
-    pub fn add_member(ctx: Context<ManageMember>, new_member: Pubkey) -> Result<()> {
+    /// Invitee accepts an invite and joins the squad with the invited role.
+    /// The inviter must still be an admin, so demoting or removing an admin
+    /// voids their outstanding invites.
+    /// The squad account grows by one member slot, funded by the invitee;
+    /// the invite is closed and its rent returned to the inviter.
+    pub fn accept_invite(ctx: Context<AcceptInvite>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let invite = &ctx.accounts.invite;
         let squad = &mut ctx.accounts.squad;
-        require!(squad.members.len() < 10, RallySquadError::TooManyMembers);
+
+        require!(!invite.is_expired(now), RallySquadError::InviteExpired);
+        require!(
+            squad.has_role(&invite.inviter, Role::Admin),
+            RallySquadError::InviterNotAdmin
+        );
+        require!(
+            squad.members.len() < squad.max_members as usize,
+            RallySquadError::TooManyMembers
+        );
         require!(
-            !squad.members.contains(&new_member),
+            squad.role_of(&invite.invitee).is_none(),
             RallySquadError::AlreadyMember
         );
 
-        squad.members.push(new_member);
-        msg!("Member {} added to squad", new_member);
+        squad.members.push(SquadMember { key: invite.invitee, role: invite.role });
+        emit!(MemberAdded {
+            squad: squad.key(),
+            member: invite.invitee,
+            role: invite.role,
+        });
+        msg!("Member {} joined squad", invite.invitee);
+        Ok(())
+    }
+
+    /// Revoke a pending invite. Only admins can do this; the invite's rent
+    /// returns to the inviter.
+    pub fn revoke_invite(ctx: Context<RevokeInvite>) -> Result<()> {
+        let invite = &ctx.accounts.invite;
+        emit!(InviteRevoked {
+            squad: invite.squad,
+            invite: invite.key(),
+            invitee: invite.invitee,
+        });
+        msg!("Invite for {} revoked", invite.invitee);
+        Ok(())
+    }
+
+    /// Remove a member from a SOL squad. Only admins can do this, and the
+    /// authority cannot be removed. The member is settled exactly as if they
+    /// had left: they receive their exit-policy share of the vault and their
+    /// state account is closed to them. A pending authority nomination of
+    /// the removed member is cancelled.
+    /// With `shrink`, the freed member slot is reallocated away and its rent
+    /// refunded to the removed member, who paid for it on `accept_invite`.
+    pub fn remove_member(ctx: Context<RemoveMember>, member: Pubkey, shrink: bool) -> Result<()> {
+        let contribution = close_member_state(
+            &ctx.accounts.member_state,
+            &ctx.accounts.removed_member,
+        )?;
+        let refund = ctx.accounts.squad.remove_exiting_member(
+            member,
+            contribution,
+            distributable_lamports(&ctx.accounts.vault)?,
+        )?;
+
+        if refund > 0 {
+            transfer_from_vault(
+                &ctx.accounts.squad,
+                &ctx.accounts.vault,
+                ctx.accounts.removed_member.to_account_info(),
+                &ctx.accounts.system_program,
+                refund,
+            )?;
+        }
+
+        emit!(MemberRemoved {
+            squad: ctx.accounts.squad.key(),
+            member,
+            mint: None,
+            refund,
+        });
+        msg!("Member {} removed from squad with {} lamports", member, refund);
+        if shrink {
+            msg!("Squad account shrunk to {} bytes", Squad::space(ctx.accounts.squad.members.len()));
+        }
+        Ok(())
+    }
+
+    /// Token-squad counterpart of `remove_member`; the share is paid in the
+    /// squad's mint to the removed member's token account.
+    pub fn remove_token_member(
+        ctx: Context<RemoveTokenMember>,
+        member: Pubkey,
+        shrink: bool,
+    ) -> Result<()> {
+        let contribution = close_member_state(
+            &ctx.accounts.member_state,
+            &ctx.accounts.removed_member,
+        )?;
+        let refund = ctx.accounts.squad.remove_exiting_member(
+            member,
+            contribution,
+            ctx.accounts.vault_token_account.amount,
+        )?;
+
+        if refund > 0 {
+            transfer_tokens_from_vault(
+                &ctx.accounts.squad,
+                &ctx.accounts.vault,
+                &ctx.accounts.vault_token_account,
+                &ctx.accounts.mint,
+                &ctx.accounts.member_token_account,
+                &ctx.accounts.token_program,
+                refund,
+            )?;
+        }
+
+        emit!(MemberRemoved {
+            squad: ctx.accounts.squad.key(),
+            member,
+            mint: ctx.accounts.squad.mint,
+            refund,
+        });
+        msg!("Member {} removed from squad with {} tokens", member, refund);
+        if shrink {
+            msg!("Squad account shrunk to {} bytes", Squad::space(ctx.accounts.squad.members.len()));
+        }
+        Ok(())
+    }
+
+    /// Leave the squad, taking the member's share of the vault as set by the
+    /// squad's exit policy. SOL squads share out the vault above its
+    /// rent-exempt minimum. The authority must hand over authority first.
+    /// The member's state account is closed and its rent returned.
+    pub fn leave_squad(ctx: Context<LeaveSquad>) -> Result<()> {
+        let member = ctx.accounts.member.key();
+        let contribution = close_member_state(
+            &ctx.accounts.member_state,
+            &ctx.accounts.member,
+        )?;
+        let refund = ctx.accounts.squad.remove_exiting_member(
+            member,
+            contribution,
+            distributable_lamports(&ctx.accounts.vault)?,
+        )?;
+
+        if refund > 0 {
+            transfer_from_vault(
+                &ctx.accounts.squad,
+                &ctx.accounts.vault,
+                ctx.accounts.member.to_account_info(),
+                &ctx.accounts.system_program,
+                refund,
+            )?;
+        }
+
+        emit!(MemberLeft {
+            squad: ctx.accounts.squad.key(),
+            member,
+            mint: None,
+            refund,
+        });
+        msg!("Member {} left squad with {} lamports", member, refund);
+        Ok(())
+    }
+
+    /// Token-squad counterpart of `leave_squad`; the share is paid in the
+    /// squad's mint.
+    pub fn leave_token_squad(ctx: Context<LeaveTokenSquad>) -> Result<()> {
+        let member = ctx.accounts.member.key();
+        let contribution = close_member_state(
+            &ctx.accounts.member_state,
+            &ctx.accounts.member,
+        )?;
+        let refund = ctx.accounts.squad.remove_exiting_member(
+            member,
+            contribution,
+            ctx.accounts.vault_token_account.amount,
+        )?;
+
+        if refund > 0 {
+            transfer_tokens_from_vault(
+                &ctx.accounts.squad,
+                &ctx.accounts.vault,
+                &ctx.accounts.vault_token_account,
+                &ctx.accounts.mint,
+                &ctx.accounts.member_token_account,
+                &ctx.accounts.token_program,
+                refund,
+            )?;
+        }
+
+        emit!(MemberLeft {
+            squad: ctx.accounts.squad.key(),
+            member,
+            mint: ctx.accounts.squad.mint,
+            refund,
+        });
+        msg!("Member {} left squad with {} tokens", member, refund);
+        Ok(())
+    }
+
+    /// Set what a leaving member takes from the vault. Only the authority
+    /// can do this.
+    pub fn set_exit_policy(ctx: Context<ManageMember>, exit_policy: ExitPolicy) -> Result<()> {
+        if let ExitPolicy::ProRataWithFee { fee_bps } = exit_policy {
+            require!(fee_bps <= BPS_DENOMINATOR, RallySquadError::InvalidExitPolicy);
+        }
+
+        let squad = &mut ctx.accounts.squad;
+        squad.exit_policy = exit_policy;
+
+        emit!(ExitPolicyUpdated {
+            squad: squad.key(),
+            exit_policy,
+        });
+        msg!("Exit policy set to {:?}", exit_policy);
+        Ok(())
+    }
+
+    /// Change a member's role. Only admins can do this; the authority always
+    /// stays an admin.
+    pub fn set_member_role(ctx: Context<SetMemberRole>, member: Pubkey, role: Role) -> Result<()> {
+        let squad = &mut ctx.accounts.squad;
+        require!(
+            member != squad.authority || role == Role::Admin,
+            RallySquadError::AuthorityMustBeAdmin
+        );
+        let entry = squad.members.iter_mut()
+            .find(|m| m.key == member)
+            .ok_or(RallySquadError::NotAMember)?;
+        entry.role = role;
+
+        emit!(MemberRoleChanged {
+            squad: squad.key(),
+            member,
+            role,
+        });
+        msg!("Member {} is now {:?}", member, role);
         Ok(())
     }
 
-    /// Remove a member from the squad. Only the authority can do this.
-    pub fn remove_member(ctx: Context<ManageMember>, member: Pubkey) -> Result<()> {
+    /// Raise or lower how many members the squad may hold, up to `MAX_MEMBERS`.
+    /// Only the authority can do this.
+    pub fn set_max_members(ctx: Context<ManageMember>, max_members: u8) -> Result<()> {
         let squad = &mut ctx.accounts.squad;
         require!(
-            member != squad.authority,
-            RallySquadError::CannotRemoveAuthority
+            max_members as usize <= MAX_MEMBERS && max_members as usize >= squad.members.len(),
+            RallySquadError::InvalidMaxMembers
         );
-        let initial_len = squad.members.len();
-        squad.members.retain(|m| *m != member);
+
+        squad.max_members = max_members;
+        emit!(MaxMembersUpdated {
+            squad: squad.key(),
+            max_members,
+        });
+        msg!("Squad member limit set to {}", max_members);
+        Ok(())
+    }
+
+    /// Configure rolling spend limits for below-threshold spends: at most
+    /// `member_spend_limit` per member and `squad_spend_limit` across the
+    /// squad in any `spend_window_secs` window, at most `MAX_SPEND_WINDOW_SECS`.
+    /// `None` leaves that side unlimited. Only the authority can do this.
+    pub fn set_spend_limits(
+        ctx: Context<ManageMember>,
+        spend_window_secs: i64,
+        member_spend_limit: Option<u64>,
+        squad_spend_limit: Option<u64>,
+    ) -> Result<()> {
         require!(
-            squad.members.len() < initial_len,
-            RallySquadError::NotAMember
+            spend_window_secs > 0 && spend_window_secs <= MAX_SPEND_WINDOW_SECS,
+            RallySquadError::InvalidSpendWindow
         );
 
-        msg!("Member {} removed from squad", member);
+        let squad = &mut ctx.accounts.squad;
+        squad.spend_window_secs = spend_window_secs;
+        squad.member_spend_limit = member_spend_limit;
+        squad.squad_spend_limit = squad_spend_limit;
+
+        emit!(SpendLimitsUpdated {
+            squad: squad.key(),
+            spend_window_secs,
+            member_spend_limit,
+            squad_spend_limit,
+        });
+        msg!(
+            "Spend limits set: {:?} per member, {:?} per squad every {} sec",
+            member_spend_limit,
+            squad_spend_limit,
+            spend_window_secs
+        );
         Ok(())
     }
 
-    /// Deposit SOL into the squad vault. Any member can deposit.
-    pub fn deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
-        require!(amount > 0, RallySquadError::InvalidAmount);
+    /// Nominate a member as the next authority. Only the current authority
+    /// can do this; the nominee takes over once they call `accept_authority`.
+    pub fn propose_authority(ctx: Context<ManageMember>, new_authority: Pubkey) -> Result<()> {
+        let squad = &mut ctx.accounts.squad;
+        require!(
+            squad.role_of(&new_authority).is_some(),
+            RallySquadError::NotAMember
+        );
 
-        let depositor = &ctx.accounts.depositor;
+        squad.pending_authority = Some(new_authority);
+        emit!(AuthorityProposed {
+            squad: squad.key(),
+            authority: squad.authority,
+            pending_authority: new_authority,
+        });
+        msg!("Authority handover to {} proposed", new_authority);
+        Ok(())
+    }
+
+    /// Pending authority accepts the handover and becomes the squad authority
+    /// (and an admin). The nominee must still be a member.
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
         let squad = &mut ctx.accounts.squad;
+        let new_authority = ctx.accounts.new_authority.key();
+        require!(
+            squad.pending_authority == Some(new_authority),
+            RallySquadError::NotPendingAuthority
+        );
+        require!(
+            squad.role_of(&new_authority).is_some(),
+            RallySquadError::NotAMember
+        );
+
+        let previous = squad.set_authority(new_authority);
+        emit!(AuthorityTransferred {
+            squad: squad.key(),
+            previous_authority: previous,
+            new_authority,
+            proposal: None,
+        });
+        msg!("Squad authority transferred from {} to {}", previous, new_authority);
+        Ok(())
+    }
 
+    /// Reassign authority through governance, e.g. when the authority has left.
+    /// Requires an executed `ProposalAction::TransferAuthority` proposal whose
+    /// recipient is the new authority, who must be a member.
+    pub fn execute_authority_transfer(ctx: Context<ExecuteAuthorityTransfer>) -> Result<()> {
+        let proposal = &ctx.accounts.proposal;
+        let new_authority = proposal.recipient;
+        ctx.accounts.squad.require_role(&ctx.accounts.member.key(), Role::Member)?;
+        require!(proposal.is_executed, RallySquadError::ProposalNotExecuted);
         require!(
-            squad.members.contains(&depositor.key()),
+            proposal.action == ProposalAction::TransferAuthority,
+            RallySquadError::ProposalMismatch
+        );
+        require!(
+            ctx.accounts.squad.role_of(&new_authority).is_some(),
             RallySquadError::NotAMember
         );
 
+        let squad_key = ctx.accounts.squad.key();
+        ctx.accounts.consumed_proposal.record(proposal, squad_key)?;
+
+        let previous = ctx.accounts.squad.set_authority(new_authority);
+        emit!(AuthorityTransferred {
+            squad: squad_key,
+            previous_authority: previous,
+            new_authority,
+            proposal: Some(proposal.key()),
+        });
+        msg!(
+            "Proposal {} transferred squad authority from {} to {}",
+            proposal.proposal_id,
+            previous,
+            new_authority
+        );
+        Ok(())
+    }
+
+    /// Deposit SOL into the squad vault. Anyone can deposit.
+    pub fn deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
+        require!(amount > 0, RallySquadError::InvalidAmount);
+
+        let depositor = &ctx.accounts.depositor;
+        let squad = &mut ctx.accounts.squad;
+
         // Transfer SOL from depositor to vault
         system_program::transfer(
             CpiContext::new(
@@ -94,171 +529,2159 @@ pub mod rally_squad {
             amount,
         )?;
 
-        squad.total_deposited = squad.total_deposited.checked_add(amount)
-            .ok_or(RallySquadError::Overflow)?;
+        let member_state = &mut ctx.accounts.member_state;
+        member_state.bind(squad.key(), depositor.key(), ctx.bumps.member_state);
+        squad.record_deposit(member_state, amount)?;
 
+        emit!(Deposited {
+            squad: squad.key(),
+            depositor: depositor.key(),
+            mint: squad.mint,
+            amount,
+            total_deposited: squad.total_deposited,
+        });
         msg!("Deposited {} lamports to squad vault", amount);
         Ok(())
     }
 
-    /// Withdraw SOL from the squad vault.
-    /// Below spend_threshold: any member can withdraw.
-    /// Above spend_threshold: requires a passed vote (caller must verify off-chain).
-    pub fn withdraw(
-        ctx: Context<Withdraw>,
-        amount: u64,
-        _vote_passed: bool, // Simplified for hackathon; production would CPI to vote program
-    ) -> Result<()> {
+    /// Withdraw SOL from the squad vault as a free spend.
+    /// Only amounts up to spend_threshold; larger payouts go through a
+    /// rally-vote `Transfer` proposal, which pays out when it executes.
+    pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
         require!(amount > 0, RallySquadError::InvalidAmount);
 
-        let squad = &ctx.accounts.squad;
-        let withdrawer = &ctx.accounts.withdrawer;
+        let withdrawer = ctx.accounts.withdrawer.key();
+        ctx.accounts.squad.require_role(&withdrawer, Role::Member)?;
 
-        require!(
-            squad.members.contains(&withdrawer.key()),
-            RallySquadError::NotAMember
+        ctx.accounts.member_state.bind(
+            ctx.accounts.squad.key(),
+            withdrawer,
+            ctx.bumps.member_state,
         );
+        check_free_spend(
+            &mut ctx.accounts.squad,
+            &mut ctx.accounts.member_state,
+            amount,
+        )?;
 
-        // Check if amount is below threshold (free spend) or requires vote
-        if amount > squad.spend_threshold {
-            require!(_vote_passed, RallySquadError::VoteRequired);
-        }
-
-        // Transfer SOL from vault to recipient
-        let squad_key = squad.key();
-        let vault_seeds = &[
-            b"vault",
-            squad_key.as_ref(),
-            &[squad.vault_bump],
-        ];
-        let signer_seeds = &[&vault_seeds[..]];
-
-        let vault_balance = ctx.accounts.vault.lamports();
-        require!(vault_balance >= amount, RallySquadError::InsufficientFunds);
+        transfer_from_vault(
+            &ctx.accounts.squad,
+            &ctx.accounts.vault,
+            ctx.accounts.recipient.to_account_info(),
+            &ctx.accounts.system_program,
+            amount,
+        )?;
 
-        **ctx.accounts.vault.to_account_info().try_borrow_mut_lamports()? -= amount;
-        **ctx.accounts.recipient.to_account_info().try_borrow_mut_lamports()? += amount;
+        let squad = &mut ctx.accounts.squad;
+        squad.record_withdrawal(&mut ctx.accounts.member_state, amount)?;
 
+        emit!(Withdrawn {
+            squad: squad.key(),
+            recipient: ctx.accounts.recipient.key(),
+            mint: None,
+            amount,
+            proposal: None,
+        });
         msg!("Withdrew {} lamports from squad vault", amount);
         Ok(())
     }
-}
 
-// === Accounts ===
+    /// Pay out an approved proposal. Called by rally-vote via CPI from
+    /// `execute_proposal`, which signs for the proposal PDA so that only a
+    /// passing vote can reach this instruction.
+    /// The payout is a squad expense: it counts in `total_withdrawn` but is
+    /// not charged to any member's ledger (see `MemberState`).
+    pub fn execute_proposal_withdrawal(ctx: Context<ExecuteProposalWithdrawal>) -> Result<()> {
+        let proposal = &ctx.accounts.proposal;
+        require!(
+            proposal.to_account_info().is_signer,
+            RallySquadError::ProposalNotExecuted
+        );
+        require!(
+            proposal.action == ProposalAction::Transfer,
+            RallySquadError::ProposalMismatch
+        );
 
-#[derive(Accounts)]
-#[instruction(name: String, members: Vec<Pubkey>)]
-pub struct InitializeSquad<'info> {
-    #[account(
-        init,
-        payer = authority,
-        space = Squad::space(members.len()),
-        seeds = [b"squad", authority.key().as_ref()],
-        bump
-    )]
-    pub squad: Account<'info, Squad>,
+        let amount = proposal.amount;
+        transfer_from_vault(
+            &ctx.accounts.squad,
+            &ctx.accounts.vault,
+            ctx.accounts.recipient.to_account_info(),
+            &ctx.accounts.system_program,
+            amount,
+        )?;
 
-    /// CHECK: PDA vault account, just holds SOL
-    #[account(
-        mut,
-        seeds = [b"vault", squad.key().as_ref()],
-        bump
-    )]
-    pub vault: SystemAccount<'info>,
+        ctx.accounts
+            .consumed_proposal
+            .record(proposal, ctx.accounts.squad.key())?;
+        let squad = &mut ctx.accounts.squad;
+        squad.total_withdrawn = squad.total_withdrawn.checked_add(amount)
+            .ok_or(RallySquadError::Overflow)?;
 
-    #[account(mut)]
-    pub authority: Signer<'info>,
+        emit!(Withdrawn {
+            squad: ctx.accounts.squad.key(),
+            recipient: ctx.accounts.recipient.key(),
+            mint: None,
+            amount,
+            proposal: Some(proposal.key()),
+        });
+        msg!(
+            "Proposal {} paid {} lamports from squad vault",
+            proposal.proposal_id,
+            amount
+        );
+        Ok(())
+    }
 
-    pub system_program: Program<'info, System>,
-}
+    /// Deposit SPL tokens into a token squad's vault. Anyone can deposit.
+    pub fn deposit_token(ctx: Context<DepositToken>, amount: u64) -> Result<()> {
+        require!(amount > 0, RallySquadError::InvalidAmount);
 
-#[derive(Accounts)]
-pub struct ManageMember<'info> {
-    #[account(
-        mut,
-        has_one = authority,
-    )]
-    pub squad: Account<'info, Squad>,
+        let depositor = &ctx.accounts.depositor;
+        let squad = &mut ctx.accounts.squad;
 
-    pub authority: Signer<'info>,
-}
+        token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.depositor_token_account.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.vault_token_account.to_account_info(),
+                    authority: depositor.to_account_info(),
+                },
+            ),
+            amount,
+            ctx.accounts.mint.decimals,
+        )?;
 
-#[derive(Accounts)]
-pub struct Deposit<'info> {
-    #[account(mut)]
-    pub squad: Account<'info, Squad>,
+        let member_state = &mut ctx.accounts.member_state;
+        member_state.bind(squad.key(), depositor.key(), ctx.bumps.member_state);
+        squad.record_deposit(member_state, amount)?;
 
-    /// CHECK: PDA vault
-    #[account(
-        mut,
-        seeds = [b"vault", squad.key().as_ref()],
-        bump = squad.vault_bump
-    )]
-    pub vault: SystemAccount<'info>,
+        emit!(Deposited {
+            squad: squad.key(),
+            depositor: depositor.key(),
+            mint: squad.mint,
+            amount,
+            total_deposited: squad.total_deposited,
+        });
+        msg!("Deposited {} tokens to squad vault", amount);
+        Ok(())
+    }
 
-    #[account(mut)]
-    pub depositor: Signer<'info>,
+    /// Withdraw SPL tokens from a token squad's vault as a free spend.
+    /// Same spend rules as `withdraw`.
+    pub fn withdraw_token(ctx: Context<WithdrawToken>, amount: u64) -> Result<()> {
+        require!(amount > 0, RallySquadError::InvalidAmount);
 
-    pub system_program: Program<'info, System>,
-}
+        let withdrawer = ctx.accounts.withdrawer.key();
+        ctx.accounts.squad.require_role(&withdrawer, Role::Member)?;
 
-#[derive(Accounts)]
-pub struct Withdraw<'info> {
-    #[account(mut)]
-    pub squad: Account<'info, Squad>,
+        ctx.accounts.member_state.bind(
+            ctx.accounts.squad.key(),
+            withdrawer,
+            ctx.bumps.member_state,
+        );
+        check_free_spend(
+            &mut ctx.accounts.squad,
+            &mut ctx.accounts.member_state,
+            amount,
+        )?;
 
-    /// CHECK: PDA vault
-    #[account(
-        mut,
-        seeds = [b"vault", squad.key().as_ref()],
-        bump = squad.vault_bump
-    )]
-    pub vault: SystemAccount<'info>,
+        transfer_tokens_from_vault(
+            &ctx.accounts.squad,
+            &ctx.accounts.vault,
+            &ctx.accounts.vault_token_account,
+            &ctx.accounts.mint,
+            &ctx.accounts.recipient_token_account,
+            &ctx.accounts.token_program,
+            amount,
+        )?;
 
-    #[account(mut)]
-    pub withdrawer: Signer<'info>,
+        let squad = &mut ctx.accounts.squad;
+        squad.record_withdrawal(&mut ctx.accounts.member_state, amount)?;
 
-    /// CHECK: Recipient of the withdrawal
-    #[account(mut)]
-    pub recipient: UncheckedAccount<'info>,
+        emit!(Withdrawn {
+            squad: squad.key(),
+            recipient: ctx.accounts.recipient_token_account.owner,
+            mint: squad.mint,
+            amount,
+            proposal: None,
+        });
+        msg!("Withdrew {} tokens from squad vault", amount);
+        Ok(())
+    }
 
-    pub system_program: Program<'info, System>,
-}
+    /// Token-squad counterpart of `execute_proposal_withdrawal`, called by
+    /// rally-vote via CPI when the squad vault holds an SPL token.
+    pub fn execute_proposal_token_withdrawal(
+        ctx: Context<ExecuteProposalTokenWithdrawal>,
+    ) -> Result<()> {
+        let proposal = &ctx.accounts.proposal;
+        require!(
+            proposal.to_account_info().is_signer,
+            RallySquadError::ProposalNotExecuted
+        );
+        require!(
+            proposal.action == ProposalAction::Transfer,
+            RallySquadError::ProposalMismatch
+        );
 
-// === State ===
+        let amount = proposal.amount;
+        transfer_tokens_from_vault(
+            &ctx.accounts.squad,
+            &ctx.accounts.vault,
+            &ctx.accounts.vault_token_account,
+            &ctx.accounts.mint,
+            &ctx.accounts.recipient_token_account,
+            &ctx.accounts.token_program,
+            amount,
+        )?;
 
-#[account]
-pub struct Squad {
-    pub authority: Pubkey,       // 32
-    pub name: String,            // 4 + 32 max
-    pub members: Vec<Pubkey>,    // 4 + (32 * 10) max
-    pub vault_bump: u8,          // 1
-    pub spend_threshold: u64,    // 8
-    pub total_deposited: u64,    // 8
-    pub created_at: i64,         // 8
-}
+        ctx.accounts
+            .consumed_proposal
+            .record(proposal, ctx.accounts.squad.key())?;
+        let squad = &mut ctx.accounts.squad;
+        squad.total_withdrawn = squad.total_withdrawn.checked_add(amount)
+            .ok_or(RallySquadError::Overflow)?;
 
-impl Squad {
-    pub fn space(num_members: usize) -> usize {
-        8 +      // discriminator
+        emit!(Withdrawn {
+            squad: ctx.accounts.squad.key(),
+            recipient: ctx.accounts.recipient_token_account.owner,
+            mint: ctx.accounts.squad.mint,
+            amount,
+            proposal: Some(proposal.key()),
+        });
+        msg!(
+            "Proposal {} paid {} tokens from squad vault",
+            proposal.proposal_id,
+            amount
+        );
+        Ok(())
+    }
+
+    /// Start a rally-stream payment stream funded from the squad vault.
+    /// The vault PDA is the stream's sender, so no individual member can
+    /// cancel it. Streams whose deposit is above spend_threshold need an
+    /// executed `ProposalAction::Stream` proposal naming the stream account
+    /// and committing to these exact terms (see `SquadStreamParams`).
+    pub fn create_squad_stream(
+        ctx: Context<CreateSquadStream>,
+        stream_id: u64,
+        rate: StreamRate,
+        start_time: i64,
+        end_time: i64,
+        cliff_time: Option<i64>,
+        unlock_amount: Option<u64>,
+        splits: Vec<SplitShare>,
+    ) -> Result<()> {
+        let squad = &ctx.accounts.squad;
+        let member = ctx.accounts.member.key();
+        squad.require_role(&member, Role::Member)?;
+
+        let params_hash = SquadStreamParams {
+            recipient: ctx.accounts.recipient.as_ref().map(|recipient| recipient.key()),
+            rate,
+            start_time,
+            end_time,
+            cliff_time,
+            unlock_amount,
+            splits: splits.clone(),
+        }
+        .hash()?;
+
+        let squad_key = squad.key();
+        let vault_seeds = &[b"vault", squad_key.as_ref(), &[squad.vault_bump]];
+        let signer_seeds = &[&vault_seeds[..]];
+
+        rally_stream::cpi::create_stream(
+            CpiContext::new_with_signer(
+                ctx.accounts.rally_stream_program.to_account_info(),
+                rally_stream::cpi::accounts::CreateStream {
+                    stream: ctx.accounts.stream.to_account_info(),
+                    stream_vault: ctx.accounts.stream_vault.to_account_info(),
+                    recipient: ctx.accounts.recipient.as_ref().map(|r| r.to_account_info()),
+                    sender: ctx.accounts.vault.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            stream_id,
+            rate,
+            start_time,
+            end_time,
+            cliff_time,
+            unlock_amount,
+            splits,
+        )?;
+
+        // The deposit is only known once rally-stream has validated the schedule
+        let total_deposited = {
+            let data = ctx.accounts.stream.try_borrow_data()?;
+            PaymentStream::try_deserialize(&mut &data[..])?.total_deposited
+        };
+
+        ctx.accounts.member_state.bind(squad_key, member, ctx.bumps.member_state);
+        check_spend_gate(
+            &mut ctx.accounts.squad,
+            &mut ctx.accounts.member_state,
+            total_deposited,
+            ctx.accounts.stream.key(),
+            ProposalAction::Stream,
+            params_hash,
+            ctx.accounts.proposal.as_ref(),
+            ctx.accounts.consumed_proposal.as_mut(),
+        )?;
+
+        emit!(SquadStreamCreated {
+            squad: squad_key,
+            stream: ctx.accounts.stream.key(),
+            amount: total_deposited,
+            proposal: ctx.accounts.proposal.as_ref().map(|proposal| proposal.key()),
+        });
+        msg!(
+            "Squad stream {} created with {} lamports from squad vault",
+            ctx.accounts.stream.key(),
+            total_deposited
+        );
+        Ok(())
+    }
+
+    /// Cancel a squad-funded stream. Requires an executed
+    /// `ProposalAction::CancelStream` proposal naming the stream account.
+    /// Split stream payees are passed through as remaining accounts; the
+    /// unstreamed refund returns to the squad vault.
+    pub fn cancel_squad_stream<'info>(
+        ctx: Context<'_, '_, '_, 'info, CancelSquadStream<'info>>,
+        close: bool,
+    ) -> Result<()> {
+        let squad = &ctx.accounts.squad;
+        squad.require_role(&ctx.accounts.member.key(), Role::Member)?;
+        require_stream_proposal(
+            &ctx.accounts.proposal,
+            ProposalAction::CancelStream,
+            ctx.accounts.stream.key(),
+        )?;
+
+        let squad_key = squad.key();
+        let vault_seeds = &[b"vault", squad_key.as_ref(), &[squad.vault_bump]];
+        let signer_seeds = &[&vault_seeds[..]];
+
+        rally_stream::cpi::cancel_stream(
+            CpiContext::new_with_signer(
+                ctx.accounts.rally_stream_program.to_account_info(),
+                rally_stream::cpi::accounts::CancelStream {
+                    stream: ctx.accounts.stream.to_account_info(),
+                    stream_vault: ctx.accounts.stream_vault.to_account_info(),
+                    sender: ctx.accounts.vault.to_account_info(),
+                    recipient: ctx.accounts.recipient.as_ref().map(|r| r.to_account_info()),
+                    mint: None,
+                    stream_token_vault: None,
+                    recipient_token_account: None,
+                    sender_token_account: None,
+                    token_program: None,
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                },
+                signer_seeds,
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            close,
+        )?;
+
+        ctx.accounts
+            .consumed_proposal
+            .record(&ctx.accounts.proposal, squad_key)?;
+
+        emit!(SquadStreamCancelled {
+            squad: squad_key,
+            stream: ctx.accounts.stream.key(),
+            proposal: ctx.accounts.proposal.key(),
+            closed: close,
+        });
+        msg!("Squad stream {} cancelled", ctx.accounts.stream.key());
+        Ok(())
+    }
+
+    /// Pause a squad-funded stream. Requires an executed
+    /// `ProposalAction::PauseStream` proposal naming the stream account.
+    pub fn pause_squad_stream(ctx: Context<UpdateSquadStream>) -> Result<()> {
+        update_squad_stream(ctx, ProposalAction::PauseStream)
+    }
+
+    /// Resume a paused squad-funded stream. Requires an executed
+    /// `ProposalAction::ResumeStream` proposal naming the stream account.
+    pub fn resume_squad_stream(ctx: Context<UpdateSquadStream>) -> Result<()> {
+        update_squad_stream(ctx, ProposalAction::ResumeStream)
+    }
+
+    /// Close a cancelled or fully withdrawn squad stream, returning any dust
+    /// and the stream accounts' rent to the squad vault. Permissionless:
+    /// rally-stream only lets a finished stream close, and funds can only
+    /// go back to the vault.
+    pub fn close_squad_stream(ctx: Context<CloseSquadStream>) -> Result<()> {
+        let squad = &ctx.accounts.squad;
+        let squad_key = squad.key();
+        let vault_seeds = &[b"vault", squad_key.as_ref(), &[squad.vault_bump]];
+        let signer_seeds = &[&vault_seeds[..]];
+
+        rally_stream::cpi::close_stream(CpiContext::new_with_signer(
+            ctx.accounts.rally_stream_program.to_account_info(),
+            rally_stream::cpi::accounts::CloseStream {
+                stream: ctx.accounts.stream.to_account_info(),
+                stream_vault: ctx.accounts.stream_vault.to_account_info(),
+                sender: ctx.accounts.vault.to_account_info(),
+                mint: None,
+                stream_token_vault: None,
+                sender_token_account: None,
+                token_program: None,
+                system_program: ctx.accounts.system_program.to_account_info(),
+            },
+            signer_seeds,
+        ))?;
+
+        emit!(SquadStreamClosed {
+            squad: squad_key,
+            stream: ctx.accounts.stream.key(),
+        });
+        msg!("Squad stream {} closed", ctx.accounts.stream.key());
+        Ok(())
+    }
+}
+
+/// Check that `proposal` is an executed `action` proposal naming `stream`.
+fn require_stream_proposal(
+    proposal: &VoteProposal,
+    action: ProposalAction,
+    stream: Pubkey,
+) -> Result<()> {
+    require!(proposal.is_executed, RallySquadError::ProposalNotExecuted);
+    require!(
+        proposal.action == action && proposal.recipient == stream,
+        RallySquadError::ProposalMismatch
+    );
+    Ok(())
+}
+
+/// Pause or resume a squad stream through rally-stream, signing as the
+/// vault PDA (the stream's sender), and consume the authorizing proposal.
+fn update_squad_stream(ctx: Context<UpdateSquadStream>, action: ProposalAction) -> Result<()> {
+    let squad = &ctx.accounts.squad;
+    squad.require_role(&ctx.accounts.member.key(), Role::Member)?;
+    require_stream_proposal(&ctx.accounts.proposal, action, ctx.accounts.stream.key())?;
+
+    let squad_key = squad.key();
+    let vault_seeds = &[b"vault", squad_key.as_ref(), &[squad.vault_bump]];
+    let signer_seeds = &[&vault_seeds[..]];
+    let cpi_ctx = CpiContext::new_with_signer(
+        ctx.accounts.rally_stream_program.to_account_info(),
+        rally_stream::cpi::accounts::UpdateStream {
+            stream: ctx.accounts.stream.to_account_info(),
+            sender: ctx.accounts.vault.to_account_info(),
+        },
+        signer_seeds,
+    );
+    let paused = action == ProposalAction::PauseStream;
+    if paused {
+        rally_stream::cpi::pause_stream(cpi_ctx)?;
+    } else {
+        rally_stream::cpi::resume_stream(cpi_ctx)?;
+    }
+
+    ctx.accounts
+        .consumed_proposal
+        .record(&ctx.accounts.proposal, squad_key)?;
+
+    emit!(SquadStreamUpdated {
+        squad: squad_key,
+        stream: ctx.accounts.stream.key(),
+        proposal: ctx.accounts.proposal.key(),
+        paused,
+    });
+    msg!(
+        "Squad stream {} {}",
+        ctx.accounts.stream.key(),
+        if paused { "paused" } else { "resumed" }
+    );
+    Ok(())
+}
+
+/// Enforce the spend threshold for squad streams.
+/// A supplied proposal is always validated and consumed, even below threshold,
+/// and must commit to `params_hash`; without one the stream is a free spend,
+/// recorded on the signer's ledger like a `withdraw`.
+fn check_spend_gate<'info>(
+    squad: &mut Account<'info, Squad>,
+    member_state: &mut Account<'info, MemberState>,
+    amount: u64,
+    recipient: Pubkey,
+    action: ProposalAction,
+    params_hash: [u8; 32],
+    proposal: Option<&Account<'info, VoteProposal>>,
+    consumed: Option<&mut Account<'info, ConsumedProposal>>,
+) -> Result<()> {
+    match (proposal, consumed) {
+        (Some(proposal), Some(consumed)) => {
+            require!(proposal.is_executed, RallySquadError::ProposalNotExecuted);
+            require!(
+                proposal.action == action
+                    && proposal.amount == amount
+                    && proposal.recipient == recipient
+                    && proposal.params_hash == params_hash,
+                RallySquadError::ProposalMismatch
+            );
+            consumed.record(proposal, squad.key())
+        }
+        (None, None) => {
+            check_free_spend(squad, member_state, amount)?;
+            squad.record_withdrawal(member_state, amount)
+        }
+        _ => err!(RallySquadError::VoteRequired),
+    }
+}
+
+/// Free spends are treasurer-only, capped at spend_threshold, and count
+/// against the member's and the squad's spend limits for the current window.
+fn check_free_spend<'info>(
+    squad: &mut Account<'info, Squad>,
+    member_state: &mut Account<'info, MemberState>,
+    amount: u64,
+) -> Result<()> {
+    require!(
+        amount <= squad.spend_threshold,
+        RallySquadError::VoteRequired
+    );
+    squad.require_role(&member_state.member, Role::Treasurer)?;
+    let now = Clock::get()?.unix_timestamp;
+    squad.charge_free_spend(member_state, amount, now)
+}
+
+/// Close an exiting member's state account, sending its rent to
+/// `destination`, and return their net contribution. The account is taken
+/// unchecked (its address is fixed by the caller's seeds) so an exit can't
+/// skip settling a ledger that exists; one never created counts as zero.
+fn close_member_state<'info>(
+    member_state: &UncheckedAccount<'info>,
+    destination: &AccountInfo<'info>,
+) -> Result<u64> {
+    if member_state.owner != &crate::ID || member_state.data_is_empty() {
+        return Ok(0);
+    }
+    let contribution = {
+        let data = member_state.try_borrow_data()?;
+        MemberState::try_deserialize(&mut &data[..])?.contribution()
+    };
+
+    let rent = member_state.lamports();
+    **destination.try_borrow_mut_lamports()? = destination.lamports().checked_add(rent)
+        .ok_or(RallySquadError::Overflow)?;
+    **member_state.try_borrow_mut_lamports()? = 0;
+    member_state.assign(&system_program::ID);
+    member_state.realloc(0, false)?;
+    Ok(contribution)
+}
+
+/// Lamports of a SOL vault that exits can share out. The rent-exempt
+/// minimum stays behind, so an exit never leaves the vault holding dust.
+fn distributable_lamports(vault: &SystemAccount) -> Result<u64> {
+    Ok(vault.lamports().saturating_sub(Rent::get()?.minimum_balance(0)))
+}
+
+/// Move lamports out of the system-owned vault PDA, signing with its seeds.
+fn transfer_from_vault<'info>(
+    squad: &Account<'info, Squad>,
+    vault: &SystemAccount<'info>,
+    to: AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    amount: u64,
+) -> Result<()> {
+    require!(vault.lamports() >= amount, RallySquadError::InsufficientFunds);
+
+    let squad_key = squad.key();
+    let vault_seeds = &[b"vault", squad_key.as_ref(), &[squad.vault_bump]];
+    let signer_seeds = &[&vault_seeds[..]];
+
+    system_program::transfer(
+        CpiContext::new_with_signer(
+            system_program.to_account_info(),
+            system_program::Transfer {
+                from: vault.to_account_info(),
+                to,
+            },
+            signer_seeds,
+        ),
+        amount,
+    )
+}
+
+/// Move tokens out of the vault token account, signing as the vault PDA.
+fn transfer_tokens_from_vault<'info>(
+    squad: &Account<'info, Squad>,
+    vault: &SystemAccount<'info>,
+    vault_token_account: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    to: &InterfaceAccount<'info, TokenAccount>,
+    token_program: &Interface<'info, TokenInterface>,
+    amount: u64,
+) -> Result<()> {
+    require!(
+        vault_token_account.amount >= amount,
+        RallySquadError::InsufficientFunds
+    );
+
+    let squad_key = squad.key();
+    let vault_seeds = &[b"vault", squad_key.as_ref(), &[squad.vault_bump]];
+    let signer_seeds = &[&vault_seeds[..]];
+
+    token_interface::transfer_checked(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: vault_token_account.to_account_info(),
+                mint: mint.to_account_info(),
+                to: to.to_account_info(),
+                authority: vault.to_account_info(),
+            },
+            signer_seeds,
+        ),
+        amount,
+        mint.decimals,
+    )
+}
+
+// === Accounts ===
+
+#[derive(Accounts)]
+pub struct InitializeSquad<'info> {
+    /// Per-authority squad id allocator, created on the authority's first squad
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = SquadCounter::SPACE,
+        seeds = [b"squad_counter", authority.key().as_ref()],
+        bump
+    )]
+    pub squad_counter: Account<'info, SquadCounter>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = Squad::space(1), // the authority; invitees pay for their own slots
+        seeds = [
+            b"squad",
+            authority.key().as_ref(),
+            squad_counter.next_squad_id.to_le_bytes().as_ref(),
+        ],
+        bump
+    )]
+    pub squad: Account<'info, Squad>,
+
+    /// CHECK: PDA vault account, just holds SOL
+    #[account(
+        mut,
+        seeds = [b"vault", squad.key().as_ref()],
+        bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(invitee: Pubkey)]
+pub struct CreateInvite<'info> {
+    #[account(
+        constraint = squad.has_role(&admin.key(), Role::Admin) @ RallySquadError::InsufficientRole,
+    )]
+    pub squad: Account<'info, Squad>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = Invite::SPACE,
+        seeds = [b"invite", squad.key().as_ref(), invitee.as_ref()],
+        bump
+    )]
+    pub invite: Account<'info, Invite>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptInvite<'info> {
+    #[account(
+        mut,
+        realloc = Squad::space(squad.members.len() + 1)
+            .max(squad.to_account_info().data_len()), // never shrink away spare slots
+        realloc::payer = invitee,
+        realloc::zero = false,
+    )]
+    pub squad: Account<'info, Squad>,
+
+    #[account(
+        mut,
+        seeds = [b"invite", squad.key().as_ref(), invitee.key().as_ref()],
+        bump = invite.bump,
+        has_one = squad,
+        has_one = invitee,
+        has_one = inviter,
+        close = inviter
+    )]
+    pub invite: Account<'info, Invite>,
+
+    #[account(mut)]
+    pub invitee: Signer<'info>,
+
+    /// CHECK: Receives the invite's rent
+    #[account(mut)]
+    pub inviter: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeInvite<'info> {
+    #[account(
+        constraint = squad.has_role(&admin.key(), Role::Admin) @ RallySquadError::InsufficientRole,
+    )]
+    pub squad: Account<'info, Squad>,
+
+    #[account(
+        mut,
+        seeds = [b"invite", squad.key().as_ref(), invite.invitee.as_ref()],
+        bump = invite.bump,
+        has_one = squad,
+        has_one = inviter,
+        close = inviter
+    )]
+    pub invite: Account<'info, Invite>,
+
+    pub admin: Signer<'info>,
+
+    /// CHECK: Receives the invite's rent
+    #[account(mut)]
+    pub inviter: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(member: Pubkey, shrink: bool)]
+pub struct RemoveMember<'info> {
+    #[account(
+        mut,
+        constraint = squad.mint.is_none() @ RallySquadError::WrongVaultAsset,
+        constraint = squad.has_role(&admin.key(), Role::Admin) @ RallySquadError::InsufficientRole,
+        realloc = if shrink {
+            Squad::space(squad.members.len().saturating_sub(1))
+        } else {
+            squad.to_account_info().data_len()
+        },
+        realloc::payer = removed_member,
+        realloc::zero = false,
+    )]
+    pub squad: Account<'info, Squad>,
+
+    /// CHECK: PDA vault
+    #[account(
+        mut,
+        seeds = [b"vault", squad.key().as_ref()],
+        bump = squad.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: The member being removed; receives their share, state rent and
+    /// the rent of their member slot on shrink
+    #[account(mut, address = member @ RallySquadError::NotAMember)]
+    pub removed_member: UncheckedAccount<'info>,
+
+    /// CHECK: Removed member's state PDA, closed if it exists
+    #[account(
+        mut,
+        seeds = [b"member", squad.key().as_ref(), member.as_ref()],
+        bump
+    )]
+    pub member_state: UncheckedAccount<'info>,
+
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(member: Pubkey, shrink: bool)]
+pub struct RemoveTokenMember<'info> {
+    #[account(
+        mut,
+        constraint = squad.has_role(&admin.key(), Role::Admin) @ RallySquadError::InsufficientRole,
+        realloc = if shrink {
+            Squad::space(squad.members.len().saturating_sub(1))
+        } else {
+            squad.to_account_info().data_len()
+        },
+        realloc::payer = removed_member,
+        realloc::zero = false,
+    )]
+    pub squad: Account<'info, Squad>,
+
+    #[account(
+        mint::token_program = token_program,
+        constraint = squad.mint == Some(mint.key()) @ RallySquadError::WrongVaultAsset,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: PDA vault, authority over the vault token account
+    #[account(
+        seeds = [b"vault", squad.key().as_ref()],
+        bump = squad.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_token", squad.key().as_ref()],
+        bump
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: The member being removed; receives their state rent and the
+    /// rent of their member slot on shrink
+    #[account(mut, address = member @ RallySquadError::NotAMember)]
+    pub removed_member: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = removed_member,
+        token::token_program = token_program,
+    )]
+    pub member_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Removed member's state PDA, closed if it exists
+    #[account(
+        mut,
+        seeds = [b"member", squad.key().as_ref(), member.as_ref()],
+        bump
+    )]
+    pub member_state: UncheckedAccount<'info>,
+
+    pub admin: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct LeaveSquad<'info> {
+    #[account(mut, constraint = squad.mint.is_none() @ RallySquadError::WrongVaultAsset)]
+    pub squad: Account<'info, Squad>,
+
+    /// CHECK: PDA vault
+    #[account(
+        mut,
+        seeds = [b"vault", squad.key().as_ref()],
+        bump = squad.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub member: Signer<'info>,
+
+    /// CHECK: Member's state PDA, closed on exit if they ever deposited or spent
+    #[account(
+        mut,
+        seeds = [b"member", squad.key().as_ref(), member.key().as_ref()],
+        bump
+    )]
+    pub member_state: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct LeaveTokenSquad<'info> {
+    #[account(mut)]
+    pub squad: Account<'info, Squad>,
+
+    #[account(
+        mint::token_program = token_program,
+        constraint = squad.mint == Some(mint.key()) @ RallySquadError::WrongVaultAsset,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: PDA vault, authority over the vault token account
+    #[account(
+        seeds = [b"vault", squad.key().as_ref()],
+        bump = squad.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_token", squad.key().as_ref()],
+        bump
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub member: Signer<'info>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = member,
+        token::token_program = token_program,
+    )]
+    pub member_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Member's state PDA, closed on exit if they ever deposited or spent
+    #[account(
+        mut,
+        seeds = [b"member", squad.key().as_ref(), member.key().as_ref()],
+        bump
+    )]
+    pub member_state: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SetMemberRole<'info> {
+    #[account(
+        mut,
+        constraint = squad.has_role(&admin.key(), Role::Admin) @ RallySquadError::InsufficientRole,
+    )]
+    pub squad: Account<'info, Squad>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ManageMember<'info> {
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pub squad: Account<'info, Squad>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    #[account(mut, constraint = squad.mint.is_none() @ RallySquadError::WrongVaultAsset)]
+    pub squad: Account<'info, Squad>,
+
+    /// CHECK: PDA vault
+    #[account(
+        mut,
+        seeds = [b"vault", squad.key().as_ref()],
+        bump = squad.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    /// Depositor's per-member state, tracking their contributions
+    #[account(
+        init_if_needed,
+        payer = depositor,
+        space = MemberState::SPACE,
+        seeds = [b"member", squad.key().as_ref(), depositor.key().as_ref()],
+        bump
+    )]
+    pub member_state: Account<'info, MemberState>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(mut, constraint = squad.mint.is_none() @ RallySquadError::WrongVaultAsset)]
+    pub squad: Account<'info, Squad>,
+
+    /// CHECK: PDA vault
+    #[account(
+        mut,
+        seeds = [b"vault", squad.key().as_ref()],
+        bump = squad.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub withdrawer: Signer<'info>,
+
+    /// Spender's per-member state, tracking their free spends this window
+    #[account(
+        init_if_needed,
+        payer = withdrawer,
+        space = MemberState::SPACE,
+        seeds = [b"member", squad.key().as_ref(), withdrawer.key().as_ref()],
+        bump
+    )]
+    pub member_state: Account<'info, MemberState>,
+
+    /// CHECK: Recipient of the withdrawal
+    #[account(mut)]
+    pub recipient: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteProposalWithdrawal<'info> {
+    #[account(mut, constraint = squad.mint.is_none() @ RallySquadError::WrongVaultAsset)]
+    pub squad: Account<'info, Squad>,
+
+    /// CHECK: PDA vault
+    #[account(
+        mut,
+        seeds = [b"vault", squad.key().as_ref()],
+        bump = squad.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Proposal PDA, signed for by rally-vote during execution
+    #[account(
+        seeds = [b"proposal", squad.key().as_ref(), proposal.proposal_id.to_le_bytes().as_ref()],
+        bump,
+        seeds::program = rally_vote_program::ID,
+        constraint = proposal.squad == squad.key() @ RallySquadError::ProposalMismatch,
+    )]
+    pub proposal: Account<'info, VoteProposal>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = ConsumedProposal::SPACE,
+        seeds = [b"consumed", proposal.key().as_ref()],
+        bump
+    )]
+    pub consumed_proposal: Account<'info, ConsumedProposal>,
+
+    /// CHECK: Must be the proposal's recipient
+    #[account(mut, address = proposal.recipient @ RallySquadError::ProposalMismatch)]
+    pub recipient: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeTokenSquad<'info> {
+    /// Per-authority squad id allocator, created on the authority's first squad
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = SquadCounter::SPACE,
+        seeds = [b"squad_counter", authority.key().as_ref()],
+        bump
+    )]
+    pub squad_counter: Account<'info, SquadCounter>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = Squad::space(1), // the authority; invitees pay for their own slots
+        seeds = [
+            b"squad",
+            authority.key().as_ref(),
+            squad_counter.next_squad_id.to_le_bytes().as_ref(),
+        ],
+        bump
+    )]
+    pub squad: Account<'info, Squad>,
+
+    /// CHECK: PDA vault, authority over the vault token account
+    #[account(
+        seeds = [b"vault", squad.key().as_ref()],
+        bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"vault_token", squad.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = vault,
+        token::token_program = token_program,
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DepositToken<'info> {
+    #[account(mut)]
+    pub squad: Account<'info, Squad>,
+
+    #[account(
+        mint::token_program = token_program,
+        constraint = squad.mint == Some(mint.key()) @ RallySquadError::WrongVaultAsset,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_token", squad.key().as_ref()],
+        bump
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = depositor,
+        token::token_program = token_program,
+    )]
+    pub depositor_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    /// Depositor's per-member state, tracking their contributions
+    #[account(
+        init_if_needed,
+        payer = depositor,
+        space = MemberState::SPACE,
+        seeds = [b"member", squad.key().as_ref(), depositor.key().as_ref()],
+        bump
+    )]
+    pub member_state: Account<'info, MemberState>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawToken<'info> {
+    #[account(mut)]
+    pub squad: Account<'info, Squad>,
+
+    #[account(
+        mint::token_program = token_program,
+        constraint = squad.mint == Some(mint.key()) @ RallySquadError::WrongVaultAsset,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: PDA vault, authority over the vault token account
+    #[account(
+        seeds = [b"vault", squad.key().as_ref()],
+        bump = squad.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_token", squad.key().as_ref()],
+        bump
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub withdrawer: Signer<'info>,
+
+    /// Spender's per-member state, tracking their free spends this window
+    #[account(
+        init_if_needed,
+        payer = withdrawer,
+        space = MemberState::SPACE,
+        seeds = [b"member", squad.key().as_ref(), withdrawer.key().as_ref()],
+        bump
+    )]
+    pub member_state: Account<'info, MemberState>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::token_program = token_program,
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteProposalTokenWithdrawal<'info> {
+    #[account(mut)]
+    pub squad: Account<'info, Squad>,
+
+    #[account(
+        mint::token_program = token_program,
+        constraint = squad.mint == Some(mint.key()) @ RallySquadError::WrongVaultAsset,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: PDA vault, authority over the vault token account
+    #[account(
+        seeds = [b"vault", squad.key().as_ref()],
+        bump = squad.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_token", squad.key().as_ref()],
+        bump
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Proposal PDA, signed for by rally-vote during execution
+    #[account(
+        seeds = [b"proposal", squad.key().as_ref(), proposal.proposal_id.to_le_bytes().as_ref()],
+        bump,
+        seeds::program = rally_vote_program::ID,
+        constraint = proposal.squad == squad.key() @ RallySquadError::ProposalMismatch,
+    )]
+    pub proposal: Account<'info, VoteProposal>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = ConsumedProposal::SPACE,
+        seeds = [b"consumed", proposal.key().as_ref()],
+        bump
+    )]
+    pub consumed_proposal: Account<'info, ConsumedProposal>,
+
+    /// Token account owned by the proposal's recipient
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = proposal.recipient,
+        token::token_program = token_program,
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateSquadStream<'info> {
+    #[account(mut, constraint = squad.mint.is_none() @ RallySquadError::WrongVaultAsset)]
+    pub squad: Account<'info, Squad>,
+
+    /// CHECK: PDA vault, funds and signs as the stream sender
+    #[account(
+        mut,
+        seeds = [b"vault", squad.key().as_ref()],
+        bump = squad.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: Stream PDA, created and validated by rally-stream
+    #[account(mut)]
+    pub stream: UncheckedAccount<'info>,
+
+    /// CHECK: Stream vault PDA, validated by rally-stream
+    #[account(mut)]
+    pub stream_vault: UncheckedAccount<'info>,
+
+    /// CHECK: Recipient of the stream; omitted for split streams
+    pub recipient: Option<UncheckedAccount<'info>>,
+
+    #[account(mut)]
+    pub member: Signer<'info>,
+
+    /// Spender's per-member state, tracking their free spends this window
+    #[account(
+        init_if_needed,
+        payer = member,
+        space = MemberState::SPACE,
+        seeds = [b"member", squad.key().as_ref(), member.key().as_ref()],
+        bump
+    )]
+    pub member_state: Account<'info, MemberState>,
+
+    /// Executed rally-vote proposal authorizing an above-threshold stream
+    #[account(
+        seeds = [b"proposal", squad.key().as_ref(), proposal.proposal_id.to_le_bytes().as_ref()],
+        bump,
+        seeds::program = rally_vote_program::ID,
+        constraint = proposal.squad == squad.key() @ RallySquadError::ProposalMismatch,
+    )]
+    pub proposal: Option<Account<'info, VoteProposal>>,
+
+    /// Replay guard: exists once the proposal has been used
+    #[account(
+        init,
+        payer = member,
+        space = ConsumedProposal::SPACE,
+        seeds = [b"consumed", proposal.as_ref().map(|proposal| proposal.key()).unwrap_or_default().as_ref()],
+        bump
+    )]
+    pub consumed_proposal: Option<Account<'info, ConsumedProposal>>,
+
+    pub rally_stream_program: Program<'info, RallyStream>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelSquadStream<'info> {
+    pub squad: Account<'info, Squad>,
+
+    /// CHECK: PDA vault, the stream sender; receives the refund
+    #[account(
+        mut,
+        seeds = [b"vault", squad.key().as_ref()],
+        bump = squad.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: Stream PDA, validated by rally-stream
+    #[account(mut)]
+    pub stream: UncheckedAccount<'info>,
+
+    /// CHECK: Stream vault PDA, validated by rally-stream
+    #[account(mut)]
+    pub stream_vault: UncheckedAccount<'info>,
+
+    /// CHECK: Stream recipient, validated by rally-stream; omitted for split streams
+    #[account(mut)]
+    pub recipient: Option<UncheckedAccount<'info>>,
+
+    #[account(mut)]
+    pub member: Signer<'info>,
+
+    /// Executed rally-vote proposal authorizing the cancellation
+    #[account(
+        seeds = [b"proposal", squad.key().as_ref(), proposal.proposal_id.to_le_bytes().as_ref()],
+        bump,
+        seeds::program = rally_vote_program::ID,
+        constraint = proposal.squad == squad.key() @ RallySquadError::ProposalMismatch,
+    )]
+    pub proposal: Account<'info, VoteProposal>,
+
+    #[account(
+        init,
+        payer = member,
+        space = ConsumedProposal::SPACE,
+        seeds = [b"consumed", proposal.key().as_ref()],
+        bump
+    )]
+    pub consumed_proposal: Account<'info, ConsumedProposal>,
+
+    pub rally_stream_program: Program<'info, RallyStream>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateSquadStream<'info> {
+    pub squad: Account<'info, Squad>,
+
+    /// CHECK: PDA vault, the stream sender
+    #[account(
+        seeds = [b"vault", squad.key().as_ref()],
+        bump = squad.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: Stream PDA, validated by rally-stream
+    #[account(mut)]
+    pub stream: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub member: Signer<'info>,
+
+    /// Executed rally-vote proposal authorizing the pause or resume
+    #[account(
+        seeds = [b"proposal", squad.key().as_ref(), proposal.proposal_id.to_le_bytes().as_ref()],
+        bump,
+        seeds::program = rally_vote_program::ID,
+        constraint = proposal.squad == squad.key() @ RallySquadError::ProposalMismatch,
+    )]
+    pub proposal: Account<'info, VoteProposal>,
+
+    #[account(
+        init,
+        payer = member,
+        space = ConsumedProposal::SPACE,
+        seeds = [b"consumed", proposal.key().as_ref()],
+        bump
+    )]
+    pub consumed_proposal: Account<'info, ConsumedProposal>,
+
+    pub rally_stream_program: Program<'info, RallyStream>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseSquadStream<'info> {
+    pub squad: Account<'info, Squad>,
+
+    /// CHECK: PDA vault, the stream sender; receives the dust and rent
+    #[account(
+        mut,
+        seeds = [b"vault", squad.key().as_ref()],
+        bump = squad.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: Stream PDA, validated by rally-stream
+    #[account(mut)]
+    pub stream: UncheckedAccount<'info>,
+
+    /// CHECK: Stream vault PDA, validated by rally-stream
+    #[account(mut)]
+    pub stream_vault: UncheckedAccount<'info>,
+
+    pub rally_stream_program: Program<'info, RallyStream>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(mut)]
+    pub squad: Account<'info, Squad>,
+
+    pub new_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteAuthorityTransfer<'info> {
+    #[account(mut)]
+    pub squad: Account<'info, Squad>,
+
+    #[account(mut)]
+    pub member: Signer<'info>,
+
+    /// Executed rally-vote proposal naming the new authority
+    #[account(
+        seeds = [b"proposal", squad.key().as_ref(), proposal.proposal_id.to_le_bytes().as_ref()],
+        bump,
+        seeds::program = rally_vote_program::ID,
+        constraint = proposal.squad == squad.key() @ RallySquadError::ProposalMismatch,
+    )]
+    pub proposal: Account<'info, VoteProposal>,
+
+    #[account(
+        init,
+        payer = member,
+        space = ConsumedProposal::SPACE,
+        seeds = [b"consumed", proposal.key().as_ref()],
+        bump
+    )]
+    pub consumed_proposal: Account<'info, ConsumedProposal>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// === State ===
+
+/// What a squad member may do. Ordered by privilege: each role can do
+/// everything the roles below it can.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Role {
+    /// Sees the squad; cannot spend, propose or vote.
+    Viewer,
+    /// Creates proposals and votes.
+    Member,
+    /// Also spends below the threshold without a vote.
+    Treasurer,
+    /// Also adds and removes members and assigns roles.
+    Admin,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct SquadMember {
+    pub key: Pubkey,             // 32
+    pub role: Role,              // 1
+}
+
+impl SquadMember {
+    pub const SIZE: usize = 32 + 1;
+}
+
+/// Member limit of a new squad; the authority can raise it with `set_max_members`.
+pub const DEFAULT_MAX_MEMBERS: u8 = 10;
+
+/// Basis-point denominator for exit fees.
+pub const BPS_DENOMINATOR: u16 = 10_000;
+
+/// Default spend limit window: 24 hours.
+pub const DEFAULT_SPEND_WINDOW_SECS: i64 = 24 * 60 * 60;
+
+/// Longest spend limit window: one year.
+pub const MAX_SPEND_WINDOW_SECS: i64 = 365 * 24 * 60 * 60;
+
+/// Buckets per spend window; spends age out one bucket at a time.
+pub const SPEND_WINDOW_BUCKETS: usize = 24;
+
+/// Hard ceiling on squad members, keeping member scans and vote snapshots cheap.
+pub const MAX_MEMBERS: usize = 50;
+
+#[account]
+pub struct Squad {
+    pub authority: Pubkey,       // 32
+    pub name: String,            // 4 + 32 max
+    pub members: Vec<SquadMember>, // 4 + (33 * members), at most max_members
+    pub vault_bump: u8,          // 1
+    pub spend_threshold: u64,    // 8
+    pub total_deposited: u64,    // 8, cumulative deposits
+    pub created_at: i64,         // 8
+    pub mint: Option<Pubkey>,    // 1 + 32, None for SOL squads
+    pub squad_id: u64,           // 8, index in the authority's SquadCounter
+    pub bump: u8,                // 1
+    pub pending_authority: Option<Pubkey>, // 1 + 32, set by propose_authority
+    pub max_members: u8,         // 1, member ceiling, at most MAX_MEMBERS
+    pub spend_window_secs: i64,  // 8, length of the spend limit window
+    pub member_spend_limit: Option<u64>, // 1 + 8, per member per window, None = unlimited
+    pub squad_spend_limit: Option<u64>,  // 1 + 8, squad-wide per window, None = unlimited
+    pub squad_spent: SpendWindow, // 216, squad-wide free spends in the rolling window
+    pub exit_policy: ExitPolicy, // 1 + 2
+    pub total_withdrawn: u64,    // 8, free spends (withdrawals and streams) and proposal payouts
+    pub total_net_contributions: u64, // 8, sum of current members' net contributions, the exit share denominator
+}
+
+impl Squad {
+    pub fn space(num_members: usize) -> usize {
+        8 +      // discriminator
         32 +     // authority
         (4 + 32) + // name (string)
-        (4 + 32 * (num_members + 1).min(10)) + // members vec
+        (4 + SquadMember::SIZE * num_members) + // members vec, grown by accept_invite, shrunk by remove_member
         1 +      // vault_bump
         8 +      // spend_threshold
         8 +      // total_deposited
         8 +      // created_at
-        64       // padding for realloc
+        (1 + 32) + // mint
+        8 +      // squad_id
+        1 +      // bump
+        (1 + 32) + // pending_authority
+        1 +      // max_members
+        8 +      // spend_window_secs
+        (1 + 8) + // member_spend_limit
+        (1 + 8) + // squad_spend_limit
+        SpendWindow::SIZE + // squad_spent
+        ExitPolicy::SIZE + // exit_policy
+        8 +      // total_withdrawn
+        8        // total_net_contributions
+    }
+
+    fn init(
+        &mut self,
+        authority: Pubkey,
+        name: String,
+        spend_threshold: u64,
+        vault_bump: u8,
+        mint: Option<Pubkey>,
+        squad_id: u64,
+        bump: u8,
+    ) -> Result<()> {
+        self.authority = authority;
+        self.name = name;
+        // Authority is always a member, as admin
+        self.members = vec![SquadMember { key: authority, role: Role::Admin }];
+        self.vault_bump = vault_bump;
+        self.spend_threshold = spend_threshold;
+        self.total_deposited = 0;
+        self.created_at = Clock::get()?.unix_timestamp;
+        self.mint = mint;
+        self.squad_id = squad_id;
+        self.bump = bump;
+        self.pending_authority = None;
+        self.max_members = DEFAULT_MAX_MEMBERS;
+        self.spend_window_secs = DEFAULT_SPEND_WINDOW_SECS;
+        self.member_spend_limit = None;
+        self.squad_spend_limit = None;
+        self.squad_spent = SpendWindow::default();
+        self.exit_policy = ExitPolicy::ProRata;
+        self.total_withdrawn = 0;
+        self.total_net_contributions = 0;
+        Ok(())
+    }
+
+    /// Count a below-threshold spend against the member's and the squad's
+    /// limits for the rolling window ending at `now`.
+    fn charge_free_spend(
+        &mut self,
+        member_state: &mut MemberState,
+        amount: u64,
+        now: i64,
+    ) -> Result<()> {
+        let window_secs = self.spend_window_secs;
+        member_state.spent.charge(amount, self.member_spend_limit, window_secs, now)?;
+        self.squad_spent.charge(amount, self.squad_spend_limit, window_secs, now)
+    }
+
+    /// Record a deposit on the member's ledger, keeping
+    /// `total_net_contributions` equal to the sum of net contributions.
+    fn record_deposit(&mut self, member_state: &mut MemberState, amount: u64) -> Result<()> {
+        let before = member_state.contribution();
+        member_state.record_deposit(amount)?;
+        self.total_deposited = self.total_deposited.checked_add(amount)
+            .ok_or(RallySquadError::Overflow)?;
+        self.update_contribution(before, member_state.contribution())
+    }
+
+    /// Record a withdrawal on the member's ledger; see `record_deposit`.
+    fn record_withdrawal(&mut self, member_state: &mut MemberState, amount: u64) -> Result<()> {
+        let before = member_state.contribution();
+        member_state.record_withdrawal(amount)?;
+        self.total_withdrawn = self.total_withdrawn.checked_add(amount)
+            .ok_or(RallySquadError::Overflow)?;
+        self.update_contribution(before, member_state.contribution())
+    }
+
+    fn update_contribution(&mut self, before: u64, after: u64) -> Result<()> {
+        self.total_net_contributions = self.total_net_contributions.saturating_sub(before)
+            .checked_add(after)
+            .ok_or(RallySquadError::Overflow)?;
+        Ok(())
+    }
+
+    /// Remove a leaving or removed member and return what they take from a
+    /// vault holding `vault_balance`, given their net `contribution`.
+    fn remove_exiting_member(
+        &mut self,
+        member: Pubkey,
+        contribution: u64,
+        vault_balance: u64,
+    ) -> Result<u64> {
+        require!(member != self.authority, RallySquadError::CannotRemoveAuthority);
+        require!(self.role_of(&member).is_some(), RallySquadError::NotAMember);
+
+        let refund = self.exit_policy.refund(
+            contribution,
+            self.total_net_contributions,
+            vault_balance,
+        );
+        self.members.retain(|m| m.key != member);
+        if self.pending_authority == Some(member) {
+            self.pending_authority = None;
+        }
+        self.total_net_contributions = self.total_net_contributions.saturating_sub(contribution);
+        Ok(refund)
+    }
+
+    /// Hand the squad to `new_authority`, who must be a member and becomes
+    /// an admin, clearing any pending handover. Returns the previous authority.
+    fn set_authority(&mut self, new_authority: Pubkey) -> Pubkey {
+        if let Some(entry) = self.members.iter_mut().find(|m| m.key == new_authority) {
+            entry.role = Role::Admin;
+        }
+        self.pending_authority = None;
+        std::mem::replace(&mut self.authority, new_authority)
+    }
+
+    pub fn role_of(&self, key: &Pubkey) -> Option<Role> {
+        self.members.iter().find(|m| m.key == *key).map(|m| m.role)
+    }
+
+    /// Whether `key` is a member with at least `min_role`.
+    pub fn has_role(&self, key: &Pubkey, min_role: Role) -> bool {
+        self.role_of(key).is_some_and(|role| role >= min_role)
+    }
+
+    /// Members allowed to vote on proposals.
+    pub fn voters(&self) -> Vec<Pubkey> {
+        self.members.iter()
+            .filter(|m| m.role >= Role::Member)
+            .map(|m| m.key)
+            .collect()
+    }
+
+    fn require_role(&self, key: &Pubkey, min_role: Role) -> Result<()> {
+        let role = self.role_of(key).ok_or(RallySquadError::NotAMember)?;
+        require!(role >= min_role, RallySquadError::InsufficientRole);
+        Ok(())
+    }
+}
+
+/// What a member takes from the vault when they leave.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExitPolicy {
+    /// Leaving members take nothing.
+    NoRefund,
+    /// Vault balance × member's net contribution / total net contributions.
+    ProRata,
+    /// Pro-rata share less `fee_bps`, which stays in the vault.
+    ProRataWithFee { fee_bps: u16 },
+}
+
+impl ExitPolicy {
+    pub const SIZE: usize = 1 + 2;
+
+    fn refund(&self, contribution: u64, total_contributed: u64, vault_balance: u64) -> u64 {
+        if total_contributed == 0 {
+            return 0;
+        }
+        let share = (vault_balance as u128 * contribution.min(total_contributed) as u128
+            / total_contributed as u128) as u64;
+        match *self {
+            ExitPolicy::NoRefund => 0,
+            ExitPolicy::ProRata => share,
+            ExitPolicy::ProRataWithFee { fee_bps } => {
+                let fee = share as u128 * fee_bps as u128 / BPS_DENOMINATOR as u128;
+                share - fee as u64
+            }
+        }
+    }
+}
+
+/// Spends in a rolling window, kept as a ring of time buckets each
+/// 1/`SPEND_WINDOW_BUCKETS` of the window long. The ring holds one extra
+/// bucket, so a spend keeps counting for at least a full window (and at most
+/// one bucket longer): no span of `window_secs` can exceed the limit.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct SpendWindow {
+    pub buckets: [u64; SPEND_WINDOW_BUCKETS + 1], // 8 * 25
+    pub bucket_secs: i64,        // 8, bucket length the ring was filled with
+    pub current_bucket: i64,     // 8, index of the newest bucket (time / bucket_secs)
+}
+
+impl SpendWindow {
+    pub const SIZE: usize = 8 * (SPEND_WINDOW_BUCKETS + 1) + 8 + 8;
+
+    /// Total still counting against the window as of the last roll.
+    pub fn spent(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// Count `amount` against the window ending at `now`, failing if that
+    /// takes it over `limit`.
+    fn charge(
+        &mut self,
+        amount: u64,
+        limit: Option<u64>,
+        window_secs: i64,
+        now: i64,
+    ) -> Result<()> {
+        self.roll(window_secs, now);
+        let spent = self.spent().checked_add(amount)
+            .ok_or(RallySquadError::Overflow)?;
+        if let Some(limit) = limit {
+            require!(spent <= limit, RallySquadError::SpendLimitExceeded);
+        }
+        let slot = self.current_bucket.rem_euclid(self.buckets.len() as i64) as usize;
+        self.buckets[slot] += amount; // bounded by `spent` above
+        Ok(())
+    }
+
+    /// Advance the ring to `now`, dropping buckets that have left the window.
+    /// A changed window length restarts the ring.
+    fn roll(&mut self, window_secs: i64, now: i64) {
+        let bucket_secs = ((window_secs + SPEND_WINDOW_BUCKETS as i64 - 1)
+            / SPEND_WINDOW_BUCKETS as i64)
+            .max(1);
+        let bucket = now.div_euclid(bucket_secs);
+        if bucket_secs != self.bucket_secs {
+            *self = SpendWindow {
+                bucket_secs,
+                current_bucket: bucket,
+                ..SpendWindow::default()
+            };
+            return;
+        }
+
+        let ring = self.buckets.len() as i64;
+        let elapsed = bucket.saturating_sub(self.current_bucket);
+        if elapsed >= ring {
+            self.buckets = [0; SPEND_WINDOW_BUCKETS + 1];
+        } else {
+            for expired in self.current_bucket + 1..=bucket {
+                self.buckets[expired.rem_euclid(ring) as usize] = 0;
+            }
+        }
+        // A clock that steps backwards keeps charging the newest bucket
+        self.current_bucket = self.current_bucket.max(bucket);
+    }
+}
+
+/// Per-member squad state and contribution ledger, created the first time
+/// the member deposits or spends.
+/// The ledger is kept by signer: `deposited` is what this member paid in and
+/// `withdrawn` is the free spends they made from the vault, whoever the
+/// recipient was, including free-spend squad streams. Vote-approved payouts
+/// and streams are squad expenses and are not charged to any member.
+/// `net` drives the exit share.
+#[account]
+pub struct MemberState {
+    pub squad: Pubkey,           // 32
+    pub member: Pubkey,          // 32
+    pub bump: u8,                // 1
+    pub spent: SpendWindow,      // 216, free spends in the rolling window
+    pub deposited: u64,          // 8, total this member deposited into the vault
+    pub withdrawn: u64,          // 8, total free spends this member signed for
+    pub net: i64,                // 8, deposited - withdrawn
+}
+
+impl MemberState {
+    pub const SPACE: usize = 8 + 32 + 32 + 1 + SpendWindow::SIZE + 8 + 8 + 8;
+
+    /// What the member's exit share is based on: net deposits, never below zero.
+    pub fn contribution(&self) -> u64 {
+        self.net.max(0) as u64
+    }
+
+    /// Record whose state this is. Idempotent, so it runs on every use.
+    fn bind(&mut self, squad: Pubkey, member: Pubkey, bump: u8) {
+        self.squad = squad;
+        self.member = member;
+        self.bump = bump;
+    }
+
+    fn record_deposit(&mut self, amount: u64) -> Result<()> {
+        self.deposited = self.deposited.checked_add(amount)
+            .ok_or(RallySquadError::Overflow)?;
+        self.net = i64::try_from(amount).ok()
+            .and_then(|amount| self.net.checked_add(amount))
+            .ok_or(RallySquadError::Overflow)?;
+        Ok(())
+    }
+
+    fn record_withdrawal(&mut self, amount: u64) -> Result<()> {
+        self.withdrawn = self.withdrawn.checked_add(amount)
+            .ok_or(RallySquadError::Overflow)?;
+        self.net = i64::try_from(amount).ok()
+            .and_then(|amount| self.net.checked_sub(amount))
+            .ok_or(RallySquadError::Overflow)?;
+        Ok(())
+    }
+}
+
+/// Pending invitation for `invitee` to join `squad`.
+#[account]
+pub struct Invite {
+    pub squad: Pubkey,           // 32
+    pub invitee: Pubkey,         // 32
+    pub inviter: Pubkey,         // 32, paid the rent
+    pub role: Role,              // 1
+    pub expires_at: Option<i64>, // 1 + 8, None = never
+    pub created_at: i64,         // 8
+    pub bump: u8,                // 1
+}
+
+impl Invite {
+    pub const SPACE: usize = 8 + 32 + 32 + 32 + 1 + (1 + 8) + 8 + 1;
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+}
+
+/// Allocates sequential squad ids for one authority.
+#[account]
+pub struct SquadCounter {
+    pub authority: Pubkey,       // 32
+    pub next_squad_id: u64,      // 8
+    pub bump: u8,                // 1
+}
+
+impl SquadCounter {
+    pub const SPACE: usize = 8 + 32 + 8 + 1;
+
+    /// Mark `next_squad_id` as taken by the squad just created.
+    fn advance(&mut self, authority: Pubkey, bump: u8) -> Result<()> {
+        self.authority = authority;
+        self.bump = bump;
+        self.next_squad_id = self.next_squad_id.checked_add(1)
+            .ok_or(RallySquadError::Overflow)?;
+        Ok(())
+    }
+}
+
+/// Marks a rally-vote proposal as spent so it cannot authorize a second withdrawal.
+#[account]
+pub struct ConsumedProposal {
+    pub proposal: Pubkey,        // 32
+    pub squad: Pubkey,           // 32
+    pub amount: u64,             // 8
+    pub recipient: Pubkey,       // 32
+    pub consumed_at: i64,        // 8
+}
+
+impl ConsumedProposal {
+    pub const SPACE: usize = 8 + 32 + 32 + 8 + 32 + 8;
+
+    fn record(&mut self, proposal: &Account<VoteProposal>, squad: Pubkey) -> Result<()> {
+        self.proposal = proposal.key();
+        self.squad = squad;
+        self.amount = proposal.amount;
+        self.recipient = proposal.recipient;
+        self.consumed_at = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+}
+
+// === rally-vote accounts ===
+
+/// rally-vote program ID. Proposals are only trusted when owned by this program.
+pub mod rally_vote_program {
+    use super::*;
+    declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnU");
+}
+
+/// What an executed rally-vote proposal authorizes.
+/// Defined here so rally-vote can import it rather than mirror it.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProposalAction {
+    /// Pay `amount` to `recipient` from the vault when the proposal executes.
+    Transfer,
+    /// Fund a squad stream depositing `amount`; `recipient` is the stream account.
+    Stream,
+    /// Cancel the squad stream at `recipient`; `amount` is unused.
+    CancelStream,
+    /// Make `recipient` the squad authority; `amount` is unused.
+    TransferAuthority,
+    /// Pause the squad stream at `recipient`; `amount` is unused.
+    PauseStream,
+    /// Resume the squad stream at `recipient`; `amount` is unused.
+    ResumeStream,
+}
+
+/// Stream terms a `ProposalAction::Stream` proposal commits to. Proposers
+/// store `hash()` of these as the proposal's `params_hash`, and
+/// `create_squad_stream` only accepts the proposal for identical terms.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SquadStreamParams {
+    pub recipient: Option<Pubkey>,
+    pub rate: StreamRate,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub cliff_time: Option<i64>,
+    pub unlock_amount: Option<u64>,
+    pub splits: Vec<SplitShare>,
+}
+
+impl SquadStreamParams {
+    /// sha256 of the Borsh-serialized terms.
+    pub fn hash(&self) -> Result<[u8; 32]> {
+        let data = self.try_to_vec()?;
+        Ok(anchor_lang::solana_program::hash::hash(&data).to_bytes())
+    }
+}
+
+/// Read-only view of `rally_vote::Proposal`.
+/// Mirrored here instead of imported so rally-squad never depends on rally-vote;
+/// rally-vote depends on this crate for its payout CPI and shared types.
+/// Must be kept in sync with the field order in rally-vote.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct VoteProposal {
+    pub squad: Pubkey,
+    pub proposer: Pubkey,
+    pub proposal_id: u64,
+    pub title: String,
+    pub description: String,
+    pub amount: u64,
+    pub recipient: Pubkey,
+    pub yes_votes: u32,
+    pub no_votes: u32,
+    pub voters: Vec<Pubkey>,
+    pub voting_deadline: i64,
+    pub is_executed: bool,
+    pub created_at: i64,
+    pub eligible_voters: Vec<Pubkey>,
+    pub action: ProposalAction,
+    pub params_hash: [u8; 32],
+}
+
+impl Discriminator for VoteProposal {
+    // sha256("account:Proposal")[..8]
+    const DISCRIMINATOR: [u8; 8] = [26, 94, 189, 187, 116, 136, 53, 33];
+}
+
+impl Owner for VoteProposal {
+    fn owner() -> Pubkey {
+        rally_vote_program::ID
+    }
+}
+
+impl AccountSerialize for VoteProposal {}
+
+impl AccountDeserialize for VoteProposal {
+    fn try_deserialize(buf: &mut &[u8]) -> Result<Self> {
+        require!(
+            buf.len() >= 8 && buf[..8] == Self::DISCRIMINATOR,
+            ErrorCode::AccountDiscriminatorMismatch
+        );
+        Self::try_deserialize_unchecked(buf)
+    }
+
+    fn try_deserialize_unchecked(buf: &mut &[u8]) -> Result<Self> {
+        let mut data: &[u8] = &buf[8..];
+        AnchorDeserialize::deserialize(&mut data)
+            .map_err(|_| ErrorCode::AccountDidNotDeserialize.into())
     }
 }
 
+// === Events ===
+
+#[event]
+pub struct SquadCreated {
+    pub squad: Pubkey,
+    pub authority: Pubkey,
+    pub squad_id: u64,
+    pub name: String,
+    pub spend_threshold: u64,
+    pub mint: Option<Pubkey>,
+}
+
+#[event]
+pub struct MemberAdded {
+    pub squad: Pubkey,
+    pub member: Pubkey,
+    pub role: Role,
+}
+
+#[event]
+pub struct InviteCreated {
+    pub squad: Pubkey,
+    pub invite: Pubkey,
+    pub invitee: Pubkey,
+    pub inviter: Pubkey,
+    pub role: Role,
+    pub expires_at: Option<i64>,
+}
+
+#[event]
+pub struct InviteRevoked {
+    pub squad: Pubkey,
+    pub invite: Pubkey,
+    pub invitee: Pubkey,
+}
+
+#[event]
+pub struct MemberRoleChanged {
+    pub squad: Pubkey,
+    pub member: Pubkey,
+    pub role: Role,
+}
+
+#[event]
+pub struct MemberRemoved {
+    pub squad: Pubkey,
+    pub member: Pubkey,
+    pub mint: Option<Pubkey>, // None for SOL
+    pub refund: u64,
+}
+
+#[event]
+pub struct Deposited {
+    pub squad: Pubkey,
+    pub depositor: Pubkey,
+    pub mint: Option<Pubkey>, // None for SOL
+    pub amount: u64,
+    pub total_deposited: u64,
+}
+
+#[event]
+pub struct Withdrawn {
+    pub squad: Pubkey,
+    pub recipient: Pubkey,
+    pub mint: Option<Pubkey>, // None for SOL
+    pub amount: u64,
+    pub proposal: Option<Pubkey>, // None for below-threshold spends
+}
+
+#[event]
+pub struct SquadStreamCreated {
+    pub squad: Pubkey,
+    pub stream: Pubkey,
+    pub amount: u64,
+    pub proposal: Option<Pubkey>,
+}
+
+#[event]
+pub struct SquadStreamCancelled {
+    pub squad: Pubkey,
+    pub stream: Pubkey,
+    pub proposal: Pubkey,
+    pub closed: bool,
+}
+
+#[event]
+pub struct SquadStreamUpdated {
+    pub squad: Pubkey,
+    pub stream: Pubkey,
+    pub proposal: Pubkey,
+    pub paused: bool,
+}
+
+#[event]
+pub struct SquadStreamClosed {
+    pub squad: Pubkey,
+    pub stream: Pubkey,
+}
+
+#[event]
+pub struct AuthorityProposed {
+    pub squad: Pubkey,
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+}
+
+#[event]
+pub struct AuthorityTransferred {
+    pub squad: Pubkey,
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
+    pub proposal: Option<Pubkey>, // Some when reassigned by governance
+}
+
+#[event]
+pub struct MaxMembersUpdated {
+    pub squad: Pubkey,
+    pub max_members: u8,
+}
+
+#[event]
+pub struct SpendLimitsUpdated {
+    pub squad: Pubkey,
+    pub spend_window_secs: i64,
+    pub member_spend_limit: Option<u64>,
+    pub squad_spend_limit: Option<u64>,
+}
+
+#[event]
+pub struct MemberLeft {
+    pub squad: Pubkey,
+    pub member: Pubkey,
+    pub mint: Option<Pubkey>, // None for SOL
+    pub refund: u64,
+}
+
+#[event]
+pub struct ExitPolicyUpdated {
+    pub squad: Pubkey,
+    pub exit_policy: ExitPolicy,
+}
+
 // === Errors ===
 
 #[error_code]
 pub enum RallySquadError {
     #[msg("Squad name must be 32 characters or less")]
     NameTooLong,
-    #[msg("Squad can have at most 10 members")]
+    #[msg("Squad has reached its member limit")]
     TooManyMembers,
     #[msg("Spend threshold must be greater than 0")]
     InvalidThreshold,
@@ -274,6 +2697,126 @@ pub enum RallySquadError {
     InsufficientFunds,
     #[msg("Withdrawal above threshold requires a passed vote")]
     VoteRequired,
+    #[msg("Proposal has not been executed")]
+    ProposalNotExecuted,
+    #[msg("Proposal does not match this withdrawal")]
+    ProposalMismatch,
     #[msg("Arithmetic overflow")]
     Overflow,
+    #[msg("Asset does not match the squad vault's denomination")]
+    WrongVaultAsset,
+    #[msg("Signer is not the pending squad authority")]
+    NotPendingAuthority,
+    #[msg("Member limit must cover current members and not exceed the ceiling")]
+    InvalidMaxMembers,
+    #[msg("Spend limit window must be between 1 second and 1 year")]
+    InvalidSpendWindow,
+    #[msg("Spend would exceed the rolling spend limit")]
+    SpendLimitExceeded,
+    #[msg("Member's role does not allow this action")]
+    InsufficientRole,
+    #[msg("The squad authority must keep the admin role")]
+    AuthorityMustBeAdmin,
+    #[msg("Exit fee cannot exceed 10000 bps")]
+    InvalidExitPolicy,
+    #[msg("Invite has expired")]
+    InviteExpired,
+    #[msg("The inviter is no longer a squad admin")]
+    InviterNotAdmin,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: i64 = 24 * 60 * 60;
+    const BUCKET: i64 = WINDOW / SPEND_WINDOW_BUCKETS as i64;
+
+    fn limit_exceeded(result: Result<()>) -> bool {
+        result == Err(RallySquadError::SpendLimitExceeded.into())
+    }
+
+    #[test]
+    fn spend_window_enforces_limit() {
+        let mut window = SpendWindow::default();
+        window.charge(60, Some(100), WINDOW, 1_000).unwrap();
+        window.charge(40, Some(100), WINDOW, 1_001).unwrap();
+        assert!(limit_exceeded(window.charge(1, Some(100), WINDOW, 1_002)));
+        assert_eq!(window.spent(), 100);
+
+        // No limit still records the spend
+        window.charge(1_000, None, WINDOW, 1_003).unwrap();
+        assert_eq!(window.spent(), 1_100);
+    }
+
+    #[test]
+    fn spend_window_rolls_instead_of_resetting() {
+        let start = 10 * WINDOW;
+        let mut window = SpendWindow::default();
+        window.charge(100, Some(100), WINDOW, start).unwrap();
+
+        // Still inside the window: a fixed window reset here would allow 2x
+        assert!(limit_exceeded(window.charge(1, Some(100), WINDOW, start + WINDOW - 1)));
+        assert!(limit_exceeded(window.charge(1, Some(100), WINDOW, start + WINDOW)));
+
+        // Aged out one bucket after the full window has passed
+        window.charge(100, Some(100), WINDOW, start + WINDOW + BUCKET).unwrap();
+        assert_eq!(window.spent(), 100);
+    }
+
+    #[test]
+    fn spend_window_ages_out_bucket_by_bucket() {
+        let start = 10 * WINDOW;
+        let mut window = SpendWindow::default();
+        window.charge(30, Some(100), WINDOW, start).unwrap();
+        window.charge(70, Some(100), WINDOW, start + WINDOW / 2).unwrap();
+
+        // Only the first spend has aged out
+        window.charge(30, Some(100), WINDOW, start + WINDOW + BUCKET).unwrap();
+        assert!(limit_exceeded(window.charge(1, Some(100), WINDOW, start + WINDOW + BUCKET)));
+
+        // Long idle: everything has aged out
+        window.charge(100, Some(100), WINDOW, start + 5 * WINDOW).unwrap();
+        assert_eq!(window.spent(), 100);
+    }
+
+    #[test]
+    fn spend_window_restarts_when_window_changes() {
+        let mut window = SpendWindow::default();
+        window.charge(100, Some(100), WINDOW, 1_000).unwrap();
+        window.charge(100, Some(100), 2 * WINDOW, 1_001).unwrap();
+        assert!(limit_exceeded(window.charge(1, Some(100), 2 * WINDOW, 1_002)));
+    }
+
+    #[test]
+    fn spend_window_handles_longest_window() {
+        let mut window = SpendWindow::default();
+        window.charge(100, Some(100), MAX_SPEND_WINDOW_SECS, i64::MAX).unwrap();
+        assert!(limit_exceeded(window.charge(1, Some(100), MAX_SPEND_WINDOW_SECS, i64::MAX)));
+    }
+
+    #[test]
+    fn exit_refund_follows_policy() {
+        assert_eq!(ExitPolicy::NoRefund.refund(50, 100, 1_000), 0);
+        assert_eq!(ExitPolicy::ProRata.refund(50, 100, 1_000), 500);
+        assert_eq!(ExitPolicy::ProRata.refund(1, 3, 1_000), 333);
+        assert_eq!(
+            ExitPolicy::ProRataWithFee { fee_bps: 1_000 }.refund(50, 100, 1_000),
+            450
+        );
+        assert_eq!(
+            ExitPolicy::ProRataWithFee { fee_bps: BPS_DENOMINATOR }.refund(50, 100, 1_000),
+            0
+        );
+    }
+
+    #[test]
+    fn exit_refund_never_exceeds_vault() {
+        // Nothing contributed: nobody has a share
+        assert_eq!(ExitPolicy::ProRata.refund(0, 0, 1_000), 0);
+        assert_eq!(ExitPolicy::ProRata.refund(0, 100, 1_000), 0);
+        // A stale contribution above the total is clamped to the whole vault
+        assert_eq!(ExitPolicy::ProRata.refund(200, 100, 1_000), 1_000);
+        assert_eq!(ExitPolicy::ProRata.refund(u64::MAX, u64::MAX, u64::MAX), u64::MAX);
+    }
 }
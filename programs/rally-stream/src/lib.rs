@@ -1,5 +1,10 @@
+#![allow(clippy::too_many_arguments)]
+
 use anchor_lang::prelude::*;
 use anchor_lang::system_program;
+use anchor_spl::token_interface::{
+    self, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
 
 declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnT");
 
@@ -10,23 +15,33 @@ pub mod rally_stream {
     use super::*;
 
     /// Create a new payment stream. Funds are deposited into a vault PDA.
+    /// The rate is either a whole amount per second or a total amount spread
+    /// over `start_time..end_time` at sub-lamport precision.
+    /// An optional cliff withholds everything until `cliff_time`, and an
+    /// optional `unlock_amount` vests in full at `start_time`.
+    /// Non-empty `splits` pays several recipients by basis-point share instead
+    /// of the single `recipient` account.
     pub fn create_stream(
         ctx: Context<CreateStream>,
         stream_id: u64,
-        amount_per_second: u64,
+        rate: StreamRate,
         start_time: i64,
         end_time: i64,
+        cliff_time: Option<i64>,
+        unlock_amount: Option<u64>,
+        splits: Vec<SplitShare>,
     ) -> Result<()> {
         let now = Clock::get()?.unix_timestamp;
-        require!(start_time >= now - 60, StreamError::InvalidStartTime); // Allow 60s grace
-        require!(end_time > start_time, StreamError::InvalidEndTime);
-        require!(amount_per_second > 0, StreamError::InvalidRate);
-
-        let duration = (end_time - start_time) as u64;
-        let total_deposit = amount_per_second.checked_mul(duration)
-            .ok_or(StreamError::Overflow)?;
-
-        require!(total_deposit > 0, StreamError::InvalidAmount);
+        let unlock_amount = unlock_amount.unwrap_or(0);
+        let (recipient, splits) = resolve_recipients(ctx.accounts.recipient.as_ref(), splits)?;
+        let (total_deposit, rate_scaled) = validate_schedule(
+            now,
+            rate,
+            start_time,
+            end_time,
+            cliff_time,
+            unlock_amount,
+        )?;
 
         // Transfer total deposit from sender to stream vault
         system_program::transfer(
@@ -42,9 +57,10 @@ pub mod rally_stream {
 
         let stream = &mut ctx.accounts.stream;
         stream.sender = ctx.accounts.sender.key();
-        stream.recipient = ctx.accounts.recipient.key();
+        stream.recipient = recipient;
         stream.stream_id = stream_id;
-        stream.amount_per_second = amount_per_second;
+        stream.amount_per_second = (rate_scaled / RATE_SCALE) as u64;
+        stream.rate_scaled = rate_scaled;
         stream.start_time = start_time;
         stream.end_time = end_time;
         stream.total_deposited = total_deposit;
@@ -52,55 +68,225 @@ pub mod rally_stream {
         stream.is_cancelled = false;
         stream.vault_bump = ctx.bumps.stream_vault;
         stream.created_at = now;
+        stream.mint = None;
+        stream.is_paused = false;
+        stream.paused_at = 0;
+        stream.total_paused_duration = 0;
+        stream.cliff_time = cliff_time;
+        stream.unlock_amount = unlock_amount;
+        stream.splits = splits;
 
+        emit!(StreamCreated {
+            stream: stream.key(),
+            sender: stream.sender,
+            recipient: stream.recipient,
+            stream_id,
+            mint: stream.mint,
+            total_deposited: total_deposit,
+            rate_scaled,
+            start_time,
+            end_time,
+            cliff_time,
+            unlock_amount,
+            splits: stream.splits.iter()
+                .map(|split| SplitShare {
+                    recipient: split.recipient,
+                    share_bps: split.share_bps,
+                })
+                .collect(),
+        });
         msg!(
-            "Stream created: {} lamports/sec from {} to {}, duration {} sec",
-            amount_per_second,
+            "Stream created: {} lamports from {} to {}, duration {} sec",
+            total_deposit,
             stream.sender,
             stream.recipient,
-            duration
+            end_time - start_time
         );
         Ok(())
     }
 
-    /// Recipient withdraws accumulated streamed funds.
-    pub fn withdraw_from_stream(ctx: Context<WithdrawFromStream>) -> Result<()> {
+    /// Create a payment stream in an SPL token (Token or Token-2022).
+    /// Funds move from the sender's token account into a token vault owned
+    /// by the stream vault PDA; amounts are in the mint's base units.
+    pub fn create_token_stream(
+        ctx: Context<CreateTokenStream>,
+        stream_id: u64,
+        rate: StreamRate,
+        start_time: i64,
+        end_time: i64,
+        cliff_time: Option<i64>,
+        unlock_amount: Option<u64>,
+        splits: Vec<SplitShare>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let unlock_amount = unlock_amount.unwrap_or(0);
+        let (recipient, splits) = resolve_recipients(ctx.accounts.recipient.as_ref(), splits)?;
+        let (total_deposit, rate_scaled) = validate_schedule(
+            now,
+            rate,
+            start_time,
+            end_time,
+            cliff_time,
+            unlock_amount,
+        )?;
+
+        // Transfer total deposit from sender to stream token vault
+        token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.sender_token_account.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.stream_token_vault.to_account_info(),
+                    authority: ctx.accounts.sender.to_account_info(),
+                },
+            ),
+            total_deposit,
+            ctx.accounts.mint.decimals,
+        )?;
+
         let stream = &mut ctx.accounts.stream;
+        stream.sender = ctx.accounts.sender.key();
+        stream.recipient = recipient;
+        stream.stream_id = stream_id;
+        stream.amount_per_second = (rate_scaled / RATE_SCALE) as u64;
+        stream.rate_scaled = rate_scaled;
+        stream.start_time = start_time;
+        stream.end_time = end_time;
+        stream.total_deposited = total_deposit;
+        stream.total_withdrawn = 0;
+        stream.is_cancelled = false;
+        stream.vault_bump = ctx.bumps.stream_vault;
+        stream.created_at = now;
+        stream.mint = Some(ctx.accounts.mint.key());
+        stream.is_paused = false;
+        stream.paused_at = 0;
+        stream.total_paused_duration = 0;
+        stream.cliff_time = cliff_time;
+        stream.unlock_amount = unlock_amount;
+        stream.splits = splits;
+
+        emit!(StreamCreated {
+            stream: stream.key(),
+            sender: stream.sender,
+            recipient: stream.recipient,
+            stream_id,
+            mint: stream.mint,
+            total_deposited: total_deposit,
+            rate_scaled,
+            start_time,
+            end_time,
+            cliff_time,
+            unlock_amount,
+            splits: stream.splits.iter()
+                .map(|split| SplitShare {
+                    recipient: split.recipient,
+                    share_bps: split.share_bps,
+                })
+                .collect(),
+        });
+        msg!(
+            "Token stream created: {} units of {} from {} to {}, duration {} sec",
+            total_deposit,
+            ctx.accounts.mint.key(),
+            stream.sender,
+            stream.recipient,
+            end_time - start_time
+        );
+        Ok(())
+    }
+
+    /// Recipient withdraws accumulated streamed funds, optionally to a
+    /// different destination (e.g. a cold wallet).
+    pub fn withdraw_from_stream(ctx: Context<WithdrawFromStream>) -> Result<()> {
+        let stream = &ctx.accounts.stream;
         let now = Clock::get()?.unix_timestamp;
 
         require!(!stream.is_cancelled, StreamError::StreamCancelled);
-        require!(
-            ctx.accounts.recipient.key() == stream.recipient,
-            StreamError::Unauthorized
-        );
 
-        // Calculate elapsed time (capped at end_time)
-        let effective_time = now.min(stream.end_time);
-        let elapsed = (effective_time - stream.start_time).max(0) as u64;
-        let total_earned = stream.amount_per_second.checked_mul(elapsed)
-            .ok_or(StreamError::Overflow)?;
-        let withdrawable = total_earned.checked_sub(stream.total_withdrawn)
-            .ok_or(StreamError::Overflow)?;
+        // Accrued so far, excluding paused intervals (capped at end_time)
+        let total_earned = stream.total_earned(now)?;
+
+        // Split streams pay each recipient their own share independently
+        let recipient = ctx.accounts.recipient.key();
+        let (split_index, withdrawable) = if stream.splits.is_empty() {
+            require!(recipient == stream.recipient, StreamError::Unauthorized);
+            let withdrawable = total_earned.checked_sub(stream.total_withdrawn)
+                .ok_or(StreamError::Overflow)?;
+            (None, withdrawable)
+        } else {
+            let index = stream.splits.iter()
+                .position(|split| split.recipient == recipient)
+                .ok_or(StreamError::Unauthorized)?;
+            let withdrawable = stream.split_earned(total_earned, index)?
+                .checked_sub(stream.splits[index].withdrawn)
+                .ok_or(StreamError::Overflow)?;
+            (Some(index), withdrawable)
+        };
 
         require!(withdrawable > 0, StreamError::NothingToWithdraw);
 
-        // Transfer from vault to recipient
-        let vault_balance = ctx.accounts.stream_vault.lamports();
+        // Transfer from vault to the recipient or their chosen destination
+        let token_vault = TokenVault::from_accounts(
+            stream,
+            ctx.accounts.mint.as_ref(),
+            ctx.accounts.stream_token_vault.as_ref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+        let destination = match (&token_vault, ctx.accounts.destination.as_ref()) {
+            (Some(_), _) => ctx
+                .accounts
+                .recipient_token_account
+                .as_ref()
+                .ok_or(StreamError::MissingTokenAccounts)?
+                .to_account_info(),
+            (None, Some(destination)) => destination.to_account_info(),
+            (None, None) => ctx.accounts.recipient.to_account_info(),
+        };
+
+        let destination_key = destination.key();
+        let vault_balance = vault_balance(&ctx.accounts.stream_vault, token_vault.as_ref());
         let transfer_amount = withdrawable.min(vault_balance);
 
-        **ctx.accounts.stream_vault.to_account_info().try_borrow_mut_lamports()? -= transfer_amount;
-        **ctx.accounts.recipient.to_account_info().try_borrow_mut_lamports()? += transfer_amount;
+        transfer_from_vault(
+            stream,
+            &ctx.accounts.stream_vault,
+            token_vault.as_ref(),
+            destination,
+            &ctx.accounts.system_program,
+            transfer_amount,
+        )?;
 
+        let stream = &mut ctx.accounts.stream;
         stream.total_withdrawn = stream.total_withdrawn.checked_add(transfer_amount)
             .ok_or(StreamError::Overflow)?;
+        if let Some(index) = split_index {
+            let split = &mut stream.splits[index];
+            split.withdrawn = split.withdrawn.checked_add(transfer_amount)
+                .ok_or(StreamError::Overflow)?;
+        }
 
-        msg!("Withdrew {} lamports from stream", transfer_amount);
+        emit!(StreamWithdrawn {
+            stream: stream.key(),
+            recipient,
+            destination: destination_key,
+            amount: transfer_amount,
+            total_withdrawn: stream.total_withdrawn,
+        });
+        msg!("Withdrew {} from stream", transfer_amount);
         Ok(())
     }
 
     /// Sender cancels the stream. Unstreamed funds are returned to sender.
-    pub fn cancel_stream(ctx: Context<CancelStream>) -> Result<()> {
-        let stream = &mut ctx.accounts.stream;
+    /// Split streams settle every recipient first; their wallets (or token
+    /// accounts) are passed as remaining accounts in split order.
+    /// With `close`, the stream and its token vault are also closed and their
+    /// rent refunded to the sender.
+    pub fn cancel_stream<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CancelStream<'info>>,
+        close: bool,
+    ) -> Result<()> {
+        let stream = &ctx.accounts.stream;
         let now = Clock::get()?.unix_timestamp;
 
         require!(!stream.is_cancelled, StreamError::StreamCancelled);
@@ -109,49 +295,525 @@ pub mod rally_stream {
             StreamError::Unauthorized
         );
 
-        // Calculate how much the recipient has earned up to now
-        let effective_time = now.min(stream.end_time);
-        let elapsed = (effective_time - stream.start_time).max(0) as u64;
-        let total_earned = stream.amount_per_second.checked_mul(elapsed)
-            .ok_or(StreamError::Overflow)?;
+        // Calculate how much the recipients have earned up to now
+        let total_earned = stream.total_earned(now)?;
+
+        let token_vault = TokenVault::from_accounts(
+            stream,
+            ctx.accounts.mint.as_ref(),
+            ctx.accounts.stream_token_vault.as_ref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+        let sender_destination = match &token_vault {
+            Some(_) => ctx
+                .accounts
+                .sender_token_account
+                .as_ref()
+                .ok_or(StreamError::MissingTokenAccounts)?
+                .to_account_info(),
+            None => ctx.accounts.sender.to_account_info(),
+        };
+
+        // Each payee with what they're owed (not yet withdrawn)
+        let payees: Vec<(AccountInfo<'info>, u64)> = if stream.splits.is_empty() {
+            let destination = match &token_vault {
+                Some(_) => ctx
+                    .accounts
+                    .recipient_token_account
+                    .as_ref()
+                    .ok_or(StreamError::MissingTokenAccounts)?
+                    .to_account_info(),
+                None => ctx
+                    .accounts
+                    .recipient
+                    .as_ref()
+                    .ok_or(StreamError::MissingRecipientAccounts)?
+                    .to_account_info(),
+            };
+            vec![(destination, total_earned.saturating_sub(stream.total_withdrawn))]
+        } else {
+            require!(
+                ctx.remaining_accounts.len() == stream.splits.len(),
+                StreamError::MissingRecipientAccounts
+            );
+            let mut payees = Vec::with_capacity(stream.splits.len());
+            for (index, (split, account)) in
+                stream.splits.iter().zip(ctx.remaining_accounts).enumerate()
+            {
+                let owner = match &token_vault {
+                    Some(_) => InterfaceAccount::<TokenAccount>::try_from(account)?.owner,
+                    None => account.key(),
+                };
+                require_keys_eq!(owner, split.recipient, StreamError::MissingRecipientAccounts);
 
-        // Amount owed to recipient (not yet withdrawn)
-        let owed_to_recipient = total_earned.saturating_sub(stream.total_withdrawn);
+                let owed = stream.split_earned(total_earned, index)?
+                    .saturating_sub(split.withdrawn);
+                payees.push((account.clone(), owed));
+            }
+            payees
+        };
 
         // Remaining in vault
-        let vault_balance = ctx.accounts.stream_vault.lamports();
-
-        // Pay recipient what they're owed
-        if owed_to_recipient > 0 && vault_balance > 0 {
-            let pay_recipient = owed_to_recipient.min(vault_balance);
-            **ctx.accounts.stream_vault.to_account_info().try_borrow_mut_lamports()? -= pay_recipient;
-            **ctx.accounts.recipient.to_account_info().try_borrow_mut_lamports()? += pay_recipient;
-            stream.total_withdrawn = stream.total_withdrawn.checked_add(pay_recipient)
-                .ok_or(StreamError::Overflow)?;
+        let mut remaining = vault_balance(&ctx.accounts.stream_vault, token_vault.as_ref());
+
+        // Pay recipients what they're owed
+        let mut paid = Vec::with_capacity(payees.len());
+        for (destination, owed) in payees {
+            let pay_recipient = owed.min(remaining);
+            if pay_recipient > 0 {
+                transfer_from_vault(
+                    stream,
+                    &ctx.accounts.stream_vault,
+                    token_vault.as_ref(),
+                    destination,
+                    &ctx.accounts.system_program,
+                    pay_recipient,
+                )?;
+            }
+            remaining -= pay_recipient;
+            paid.push(pay_recipient);
         }
 
         // Return remaining funds to sender
-        let remaining = ctx.accounts.stream_vault.lamports();
         if remaining > 0 {
-            **ctx.accounts.stream_vault.to_account_info().try_borrow_mut_lamports()? -= remaining;
-            **ctx.accounts.sender.to_account_info().try_borrow_mut_lamports()? += remaining;
+            transfer_from_vault(
+                stream,
+                &ctx.accounts.stream_vault,
+                token_vault.as_ref(),
+                sender_destination,
+                &ctx.accounts.system_program,
+                remaining,
+            )?;
         }
 
+        let stream = &mut ctx.accounts.stream;
+        for (index, pay_recipient) in paid.iter().enumerate() {
+            stream.total_withdrawn = stream.total_withdrawn.checked_add(*pay_recipient)
+                .ok_or(StreamError::Overflow)?;
+            if let Some(split) = stream.splits.get_mut(index) {
+                split.withdrawn = split.withdrawn.checked_add(*pay_recipient)
+                    .ok_or(StreamError::Overflow)?;
+            }
+        }
         stream.is_cancelled = true;
-        msg!("Stream cancelled. Returned {} lamports to sender", remaining);
+        emit!(StreamCancelled {
+            stream: stream.key(),
+            sender: stream.sender,
+            paid_to_recipients: paid.iter().sum(),
+            refunded: remaining,
+            closed: close,
+        });
+        msg!("Stream cancelled. Returned {} to sender", remaining);
+
+        if close {
+            close_stream_accounts(
+                stream,
+                &ctx.accounts.stream_vault,
+                token_vault.as_ref(),
+                ctx.accounts.sender.to_account_info(),
+            )?;
+            msg!("Stream closed");
+        }
+        Ok(())
+    }
+
+    /// Sender closes a cancelled or fully withdrawn stream, sweeping any dust
+    /// left in the vault and reclaiming the rent of the stream accounts.
+    pub fn close_stream(ctx: Context<CloseStream>) -> Result<()> {
+        let stream = &ctx.accounts.stream;
+
+        require!(
+            stream.is_cancelled || stream.total_withdrawn >= stream.total_deposited,
+            StreamError::StreamNotFinished
+        );
+
+        let token_vault = TokenVault::from_accounts(
+            stream,
+            ctx.accounts.mint.as_ref(),
+            ctx.accounts.stream_token_vault.as_ref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+
+        let dust = vault_balance(&ctx.accounts.stream_vault, token_vault.as_ref());
+        if dust > 0 {
+            let destination = match &token_vault {
+                Some(_) => ctx
+                    .accounts
+                    .sender_token_account
+                    .as_ref()
+                    .ok_or(StreamError::MissingTokenAccounts)?
+                    .to_account_info(),
+                None => ctx.accounts.sender.to_account_info(),
+            };
+            transfer_from_vault(
+                stream,
+                &ctx.accounts.stream_vault,
+                token_vault.as_ref(),
+                destination,
+                &ctx.accounts.system_program,
+                dust,
+            )?;
+        }
+
+        close_stream_accounts(
+            stream,
+            &ctx.accounts.stream_vault,
+            token_vault.as_ref(),
+            ctx.accounts.sender.to_account_info(),
+        )?;
+
+        emit!(StreamClosed {
+            stream: stream.key(),
+            sender: stream.sender,
+            swept: dust,
+        });
+        msg!("Stream closed. Swept {} to sender", dust);
+        Ok(())
+    }
+
+    /// Recipient permanently assigns the stream (or, for split streams, their
+    /// share) to a new recipient, who receives all future withdrawals and any
+    /// payout on cancel.
+    pub fn transfer_stream_recipient(
+        ctx: Context<TransferStreamRecipient>,
+        new_recipient: Pubkey,
+    ) -> Result<()> {
+        let stream = &mut ctx.accounts.stream;
+        let previous = ctx.accounts.recipient.key();
+
+        require!(!stream.is_cancelled, StreamError::StreamCancelled);
+
+        if stream.splits.is_empty() {
+            require!(previous == stream.recipient, StreamError::Unauthorized);
+            stream.recipient = new_recipient;
+        } else {
+            require!(
+                !stream.splits.iter().any(|split| split.recipient == new_recipient),
+                StreamError::InvalidSplits
+            );
+            let split = stream.splits.iter_mut()
+                .find(|split| split.recipient == previous)
+                .ok_or(StreamError::Unauthorized)?;
+            split.recipient = new_recipient;
+        }
+
+        emit!(StreamRecipientTransferred {
+            stream: stream.key(),
+            previous_recipient: previous,
+            new_recipient,
+        });
+        msg!("Stream recipient transferred from {} to {}", previous, new_recipient);
+        Ok(())
+    }
+
+    /// Sender pauses the stream. Nothing accrues until it is resumed.
+    pub fn pause_stream(ctx: Context<UpdateStream>) -> Result<()> {
+        let stream = &mut ctx.accounts.stream;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(!stream.is_cancelled, StreamError::StreamCancelled);
+        require!(!stream.is_paused, StreamError::StreamPaused);
+        require!(now < stream.end_time, StreamError::StreamEnded);
+
+        stream.record_pause(now);
+
+        emit!(StreamPaused {
+            stream: stream.key(),
+            paused_at: now,
+        });
+        msg!("Stream paused at {}", now);
+        Ok(())
+    }
+
+    /// Sender resumes a paused stream. The end time is pushed out by the
+    /// paused interval so the recipient still receives the full deposit.
+    pub fn resume_stream(ctx: Context<UpdateStream>) -> Result<()> {
+        let stream = &mut ctx.accounts.stream;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(!stream.is_cancelled, StreamError::StreamCancelled);
+        require!(stream.is_paused, StreamError::StreamNotPaused);
+
+        let paused_for = stream.record_resume(now)?;
+
+        emit!(StreamResumed {
+            stream: stream.key(),
+            paused_for,
+            end_time: stream.end_time,
+        });
+        msg!("Stream resumed after {} sec, new end time {}", paused_for, stream.end_time);
+        Ok(())
+    }
+
+    /// Deposit more funds into a running stream and push end_time out by
+    /// as many whole seconds as `amount` covers at the stream's rate; any
+    /// remainder is paid at end_time. Anyone can top up a stream.
+    pub fn top_up_stream(ctx: Context<TopUpStream>, amount: u64) -> Result<()> {
+        let stream = &ctx.accounts.stream;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(amount > 0, StreamError::InvalidAmount);
+        require!(!stream.is_cancelled, StreamError::StreamCancelled);
+        require!(now < stream.end_time, StreamError::StreamEnded);
+
+        let token_vault = TokenVault::from_accounts(
+            stream,
+            ctx.accounts.mint.as_ref(),
+            ctx.accounts.stream_token_vault.as_ref(),
+            ctx.accounts.token_program.as_ref(),
+        )?;
+        match token_vault {
+            Some(token_vault) => {
+                let funder_token_account = ctx
+                    .accounts
+                    .funder_token_account
+                    .as_ref()
+                    .ok_or(StreamError::MissingTokenAccounts)?;
+                token_interface::transfer_checked(
+                    CpiContext::new(
+                        token_vault.token_program.to_account_info(),
+                        TransferChecked {
+                            from: funder_token_account.to_account_info(),
+                            mint: token_vault.mint.to_account_info(),
+                            to: token_vault.vault.to_account_info(),
+                            authority: ctx.accounts.funder.to_account_info(),
+                        },
+                    ),
+                    amount,
+                    token_vault.mint.decimals,
+                )?;
+            }
+            None => {
+                system_program::transfer(
+                    CpiContext::new(
+                        ctx.accounts.system_program.to_account_info(),
+                        system_program::Transfer {
+                            from: ctx.accounts.funder.to_account_info(),
+                            to: ctx.accounts.stream_vault.to_account_info(),
+                        },
+                    ),
+                    amount,
+                )?;
+            }
+        }
+
+        let stream = &mut ctx.accounts.stream;
+        let added_seconds = stream.record_top_up(amount)?;
+
+        emit!(StreamToppedUp {
+            stream: stream.key(),
+            funder: ctx.accounts.funder.key(),
+            amount,
+            end_time: stream.end_time,
+            total_deposited: stream.total_deposited,
+        });
+        msg!(
+            "Stream topped up by {}, extended {} sec to {}",
+            amount,
+            added_seconds,
+            stream.end_time
+        );
         Ok(())
     }
 }
 
+/// Check a stream schedule and return the total deposit it requires
+/// together with its fixed-point rate (see `RATE_SCALE`).
+fn validate_schedule(
+    now: i64,
+    rate: StreamRate,
+    start_time: i64,
+    end_time: i64,
+    cliff_time: Option<i64>,
+    unlock_amount: u64,
+) -> Result<(u64, u128)> {
+    require!(start_time >= now - 60, StreamError::InvalidStartTime); // Allow 60s grace
+    require!(end_time > start_time, StreamError::InvalidEndTime);
+    if let Some(cliff_time) = cliff_time {
+        require!(
+            cliff_time >= start_time && cliff_time <= end_time,
+            StreamError::InvalidCliff
+        );
+    }
+
+    let duration = (end_time - start_time) as u64;
+    let (streamed_amount, rate_scaled) = match rate {
+        StreamRate::PerSecond(amount_per_second) => {
+            require!(amount_per_second > 0, StreamError::InvalidRate);
+            let streamed_amount = amount_per_second.checked_mul(duration)
+                .ok_or(StreamError::Overflow)?;
+            (streamed_amount, amount_per_second as u128 * RATE_SCALE)
+        }
+        StreamRate::TotalAmount(total_amount) => {
+            let rate_scaled = total_amount as u128 * RATE_SCALE / duration as u128;
+            require!(rate_scaled > 0, StreamError::InvalidRate);
+            (total_amount, rate_scaled)
+        }
+    };
+    let total_deposit = streamed_amount.checked_add(unlock_amount)
+        .ok_or(StreamError::Overflow)?;
+
+    require!(total_deposit > 0, StreamError::InvalidAmount);
+    Ok((total_deposit, rate_scaled))
+}
+
+/// Resolve who a new stream pays: the single `recipient` account, or, when
+/// `splits` is non-empty, the validated split list.
+fn resolve_recipients(
+    recipient: Option<&UncheckedAccount>,
+    splits: Vec<SplitShare>,
+) -> Result<(Pubkey, Vec<StreamSplit>)> {
+    if splits.is_empty() {
+        let recipient = recipient.ok_or(StreamError::MissingRecipientAccounts)?;
+        return Ok((recipient.key(), vec![]));
+    }
+
+    require!(splits.len() <= MAX_SPLITS, StreamError::InvalidSplits);
+    let mut total_bps: u32 = 0;
+    for (index, split) in splits.iter().enumerate() {
+        require!(split.share_bps > 0, StreamError::InvalidSplits);
+        require!(
+            !splits[..index].iter().any(|other| other.recipient == split.recipient),
+            StreamError::InvalidSplits
+        );
+        total_bps += split.share_bps as u32;
+    }
+    require!(total_bps == BPS_DENOMINATOR as u32, StreamError::InvalidSplits);
+
+    let splits = splits
+        .into_iter()
+        .map(|split| StreamSplit {
+            recipient: split.recipient,
+            share_bps: split.share_bps,
+            withdrawn: 0,
+        })
+        .collect();
+    Ok((Pubkey::default(), splits))
+}
+
+/// Token accounts used to pay out of a token stream's vault.
+struct TokenVault<'a, 'info> {
+    mint: &'a InterfaceAccount<'info, Mint>,
+    vault: &'a InterfaceAccount<'info, TokenAccount>,
+    token_program: &'a Interface<'info, TokenInterface>,
+}
+
+impl<'a, 'info> TokenVault<'a, 'info> {
+    /// `None` for SOL streams; token streams must supply all token accounts.
+    fn from_accounts(
+        stream: &PaymentStream,
+        mint: Option<&'a InterfaceAccount<'info, Mint>>,
+        vault: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+        token_program: Option<&'a Interface<'info, TokenInterface>>,
+    ) -> Result<Option<Self>> {
+        let Some(stream_mint) = stream.mint else {
+            return Ok(None);
+        };
+        match (mint, vault, token_program) {
+            (Some(mint), Some(vault), Some(token_program)) => {
+                require_keys_eq!(mint.key(), stream_mint, StreamError::MintMismatch);
+                Ok(Some(Self { mint, vault, token_program }))
+            }
+            _ => err!(StreamError::MissingTokenAccounts),
+        }
+    }
+}
+
+/// Funds currently held for the stream: vault lamports or vault token balance.
+fn vault_balance(stream_vault: &SystemAccount, token_vault: Option<&TokenVault>) -> u64 {
+    match token_vault {
+        Some(token_vault) => token_vault.vault.amount,
+        None => stream_vault.lamports(),
+    }
+}
+
+/// Move funds out of the stream vault, signing as the vault PDA.
+/// `to` is a wallet for SOL streams and a token account for token streams.
+fn transfer_from_vault<'info>(
+    stream: &Account<'info, PaymentStream>,
+    stream_vault: &SystemAccount<'info>,
+    token_vault: Option<&TokenVault<'_, 'info>>,
+    to: AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    amount: u64,
+) -> Result<()> {
+    let stream_key = stream.key();
+    let vault_seeds = &[b"stream_vault", stream_key.as_ref(), &[stream.vault_bump]];
+    let signer_seeds = &[&vault_seeds[..]];
+
+    match token_vault {
+        Some(token_vault) => token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                token_vault.token_program.to_account_info(),
+                TransferChecked {
+                    from: token_vault.vault.to_account_info(),
+                    mint: token_vault.mint.to_account_info(),
+                    to,
+                    authority: stream_vault.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+            token_vault.mint.decimals,
+        ),
+        None => system_program::transfer(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: stream_vault.to_account_info(),
+                    to,
+                },
+                signer_seeds,
+            ),
+            amount,
+        ),
+    }
+}
+
+/// Close the token vault (if any) and the stream account, sending their rent
+/// to the sender. The vault must already be empty.
+fn close_stream_accounts<'info>(
+    stream: &Account<'info, PaymentStream>,
+    stream_vault: &SystemAccount<'info>,
+    token_vault: Option<&TokenVault<'_, 'info>>,
+    sender: AccountInfo<'info>,
+) -> Result<()> {
+    if let Some(token_vault) = token_vault {
+        let stream_key = stream.key();
+        let vault_seeds = &[b"stream_vault", stream_key.as_ref(), &[stream.vault_bump]];
+        let signer_seeds = &[&vault_seeds[..]];
+
+        token_interface::close_account(CpiContext::new_with_signer(
+            token_vault.token_program.to_account_info(),
+            CloseAccount {
+                account: token_vault.vault.to_account_info(),
+                destination: sender.clone(),
+                authority: stream_vault.to_account_info(),
+            },
+            signer_seeds,
+        ))?;
+    }
+
+    stream.close(sender)
+}
+
 // === Accounts ===
 
 #[derive(Accounts)]
-#[instruction(stream_id: u64)]
+#[instruction(
+    stream_id: u64,
+    rate: StreamRate,
+    start_time: i64,
+    end_time: i64,
+    cliff_time: Option<i64>,
+    unlock_amount: Option<u64>,
+    splits: Vec<SplitShare>,
+)]
 pub struct CreateStream<'info> {
     #[account(
         init,
         payer = sender,
-        space = PaymentStream::SPACE,
+        space = PaymentStream::space(splits.len()),
         seeds = [b"stream", sender.key().as_ref(), stream_id.to_le_bytes().as_ref()],
         bump
     )]
@@ -165,8 +827,8 @@ pub struct CreateStream<'info> {
     )]
     pub stream_vault: SystemAccount<'info>,
 
-    /// CHECK: Recipient of the payment stream
-    pub recipient: UncheckedAccount<'info>,
+    /// CHECK: Recipient of the payment stream; omitted for split streams
+    pub recipient: Option<UncheckedAccount<'info>>,
 
     #[account(mut)]
     pub sender: Signer<'info>,
@@ -174,6 +836,66 @@ pub struct CreateStream<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+#[instruction(
+    stream_id: u64,
+    rate: StreamRate,
+    start_time: i64,
+    end_time: i64,
+    cliff_time: Option<i64>,
+    unlock_amount: Option<u64>,
+    splits: Vec<SplitShare>,
+)]
+pub struct CreateTokenStream<'info> {
+    #[account(
+        init,
+        payer = sender,
+        space = PaymentStream::space(splits.len()),
+        seeds = [b"stream", sender.key().as_ref(), stream_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub stream: Account<'info, PaymentStream>,
+
+    /// CHECK: PDA vault, authority over the stream token vault
+    #[account(
+        seeds = [b"stream_vault", stream.key().as_ref()],
+        bump
+    )]
+    pub stream_vault: SystemAccount<'info>,
+
+    #[account(mint::token_program = token_program)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = sender,
+        seeds = [b"stream_token_vault", stream.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = stream_vault,
+        token::token_program = token_program,
+    )]
+    pub stream_token_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = sender,
+        token::token_program = token_program,
+    )]
+    pub sender_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Recipient of the payment stream; omitted for split streams
+    pub recipient: Option<UncheckedAccount<'info>>,
+
+    #[account(mut)]
+    pub sender: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    pub system_program: Program<'info, System>,
+}
+
 #[derive(Accounts)]
 pub struct WithdrawFromStream<'info> {
     #[account(mut)]
@@ -190,6 +912,27 @@ pub struct WithdrawFromStream<'info> {
     #[account(mut)]
     pub recipient: Signer<'info>,
 
+    /// CHECK: SOL streams only: wallet to pay instead of the recipient
+    #[account(mut)]
+    pub destination: Option<UncheckedAccount<'info>>,
+
+    /// Token streams only: the stream's mint
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+
+    /// Token streams only: token vault owned by the stream vault PDA
+    #[account(
+        mut,
+        seeds = [b"stream_token_vault", stream.key().as_ref()],
+        bump
+    )]
+    pub stream_token_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Token streams only: token account to pay, chosen by the recipient
+    #[account(mut)]
+    pub recipient_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+
     pub system_program: Program<'info, System>,
 }
 
@@ -209,21 +952,176 @@ pub struct CancelStream<'info> {
     #[account(mut)]
     pub sender: Signer<'info>,
 
-    /// CHECK: Recipient gets owed funds on cancel
+    /// CHECK: Recipient gets owed funds on cancel; omitted for split streams
+    #[account(mut, address = stream.recipient @ StreamError::Unauthorized)]
+    pub recipient: Option<UncheckedAccount<'info>>,
+
+    /// Token streams only: the stream's mint
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+
+    /// Token streams only: token vault owned by the stream vault PDA
+    #[account(
+        mut,
+        seeds = [b"stream_token_vault", stream.key().as_ref()],
+        bump
+    )]
+    pub stream_token_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Token streams only: recipient's token account
+    #[account(
+        mut,
+        constraint = recipient_token_account.owner == stream.recipient @ StreamError::Unauthorized,
+    )]
+    pub recipient_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Token streams only: sender's token account for the refund
+    #[account(
+        mut,
+        constraint = sender_token_account.owner == stream.sender @ StreamError::Unauthorized,
+    )]
+    pub sender_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseStream<'info> {
+    #[account(mut, has_one = sender @ StreamError::Unauthorized)]
+    pub stream: Account<'info, PaymentStream>,
+
+    /// CHECK: PDA vault
+    #[account(
+        mut,
+        seeds = [b"stream_vault", stream.key().as_ref()],
+        bump = stream.vault_bump
+    )]
+    pub stream_vault: SystemAccount<'info>,
+
     #[account(mut)]
-    pub recipient: UncheckedAccount<'info>,
+    pub sender: Signer<'info>,
+
+    /// Token streams only: the stream's mint
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+
+    /// Token streams only: token vault owned by the stream vault PDA
+    #[account(
+        mut,
+        seeds = [b"stream_token_vault", stream.key().as_ref()],
+        bump
+    )]
+    pub stream_token_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Token streams only: sender's token account for any swept dust
+    #[account(
+        mut,
+        constraint = sender_token_account.owner == stream.sender @ StreamError::Unauthorized,
+    )]
+    pub sender_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TransferStreamRecipient<'info> {
+    #[account(mut)]
+    pub stream: Account<'info, PaymentStream>,
+
+    pub recipient: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateStream<'info> {
+    #[account(mut, has_one = sender @ StreamError::Unauthorized)]
+    pub stream: Account<'info, PaymentStream>,
+
+    pub sender: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TopUpStream<'info> {
+    #[account(mut)]
+    pub stream: Account<'info, PaymentStream>,
+
+    /// CHECK: PDA vault
+    #[account(
+        mut,
+        seeds = [b"stream_vault", stream.key().as_ref()],
+        bump = stream.vault_bump
+    )]
+    pub stream_vault: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub funder: Signer<'info>,
+
+    /// Token streams only: the stream's mint
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+
+    /// Token streams only: token vault owned by the stream vault PDA
+    #[account(
+        mut,
+        seeds = [b"stream_token_vault", stream.key().as_ref()],
+        bump
+    )]
+    pub stream_token_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Token streams only: funder's token account
+    #[account(mut)]
+    pub funder_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
 
     pub system_program: Program<'info, System>,
 }
 
 // === State ===
 
+/// Fixed-point scale of `PaymentStream::rate_scaled`: units per second × 1e9.
+pub const RATE_SCALE: u128 = 1_000_000_000;
+
+/// Basis-point denominator for split shares.
+pub const BPS_DENOMINATOR: u16 = 10_000;
+
+/// Maximum number of recipients on a split stream.
+pub const MAX_SPLITS: usize = 10;
+
+/// A recipient's share of a split stream, as passed to `create_stream`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct SplitShare {
+    pub recipient: Pubkey,
+    pub share_bps: u16,
+}
+
+/// A split stream recipient with their own withdrawal counter.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct StreamSplit {
+    pub recipient: Pubkey,        // 32
+    pub share_bps: u16,           // 2
+    pub withdrawn: u64,           // 8
+}
+
+impl StreamSplit {
+    pub const SIZE: usize = 32 + 2 + 8;
+}
+
+/// How a stream's rate is specified at creation.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub enum StreamRate {
+    /// Whole lamports (or token base units) per second.
+    PerSecond(u64),
+    /// Total amount streamed evenly between start_time and end_time.
+    TotalAmount(u64),
+}
+
 #[account]
 pub struct PaymentStream {
     pub sender: Pubkey,           // 32
     pub recipient: Pubkey,        // 32
     pub stream_id: u64,           // 8
-    pub amount_per_second: u64,   // 8
+    pub amount_per_second: u64,   // 8, whole units of rate_scaled
     pub start_time: i64,          // 8
     pub end_time: i64,            // 8
     pub total_deposited: u64,     // 8
@@ -231,10 +1129,183 @@ pub struct PaymentStream {
     pub is_cancelled: bool,       // 1
     pub vault_bump: u8,           // 1
     pub created_at: i64,          // 8
+    pub mint: Option<Pubkey>,     // 1 + 32, None for SOL streams
+    pub is_paused: bool,          // 1
+    pub paused_at: i64,           // 8
+    pub total_paused_duration: i64, // 8
+    pub cliff_time: Option<i64>,  // 1 + 8
+    pub unlock_amount: u64,       // 8
+    pub rate_scaled: u128,        // 16
+    pub splits: Vec<StreamSplit>, // 4 + (42 * splits), empty for single-recipient streams
 }
 
 impl PaymentStream {
-    pub const SPACE: usize = 8 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 1 + 1 + 8 + 32; // + padding
+    pub fn space(num_splits: usize) -> usize {
+        8 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 1 + 1 + 8 + (1 + 32) + 1 + 8 + 8 + (1 + 8) + 8 + 16
+            + (4 + StreamSplit::SIZE * num_splits)
+            + 32 // padding
+    }
+
+    /// Seconds of accrual up to `now`, excluding paused time, capped at end_time.
+    pub fn streamed_seconds(&self, now: i64) -> u64 {
+        let accrual_time = if self.is_paused { self.paused_at } else { now };
+        let effective_time = accrual_time.min(self.end_time);
+        (effective_time - self.start_time - self.total_paused_duration).max(0) as u64
+    }
+
+    /// Seconds the stream runs for in total, excluding paused time.
+    pub fn stream_duration(&self) -> u64 {
+        (self.end_time - self.start_time - self.total_paused_duration).max(0) as u64
+    }
+
+    /// Total amount vested to the recipient up to `now`: nothing before the
+    /// cliff or start_time, then the upfront unlock plus the streamed amount.
+    /// Rounding remainders of the fixed-point rate are paid at end_time, so a
+    /// finished stream has earned exactly its deposit.
+    pub fn total_earned(&self, now: i64) -> Result<u64> {
+        if now < self.start_time || self.cliff_time.is_some_and(|cliff| now < cliff) {
+            return Ok(0);
+        }
+
+        let streamed_amount = self.total_deposited.checked_sub(self.unlock_amount)
+            .ok_or(StreamError::Overflow)?;
+        let seconds = self.streamed_seconds(now);
+        let streamed = if seconds >= self.stream_duration() {
+            streamed_amount
+        } else {
+            let accrued = self.rate_scaled.checked_mul(seconds as u128)
+                .ok_or(StreamError::Overflow)?
+                / RATE_SCALE;
+            u64::try_from(accrued).map_err(|_| StreamError::Overflow)?.min(streamed_amount)
+        };
+
+        Ok(streamed.checked_add(self.unlock_amount)
+            .ok_or(StreamError::Overflow)?)
+    }
+
+    /// Stop accrual at `now`.
+    fn record_pause(&mut self, now: i64) {
+        self.is_paused = true;
+        self.paused_at = now;
+    }
+
+    /// Restart accrual at `now`, pushing end_time out by the paused interval
+    /// so the full deposit still streams. Returns the paused interval.
+    fn record_resume(&mut self, now: i64) -> Result<i64> {
+        // Time paused before start_time never accrued anyway, so it doesn't count
+        let paused_for = (now.max(self.start_time)
+            - self.paused_at.max(self.start_time))
+            .max(0);
+        self.total_paused_duration = self.total_paused_duration.checked_add(paused_for)
+            .ok_or(StreamError::Overflow)?;
+        self.end_time = self.end_time.checked_add(paused_for)
+            .ok_or(StreamError::Overflow)?;
+        self.is_paused = false;
+        self.paused_at = 0;
+        Ok(paused_for)
+    }
+
+    /// Add `amount` to the deposit and extend end_time by the whole seconds
+    /// it covers at the stream's rate. Returns the added seconds.
+    fn record_top_up(&mut self, amount: u64) -> Result<i64> {
+        let added_seconds = (amount as u128)
+            .checked_mul(RATE_SCALE)
+            .map(|scaled| scaled / self.rate_scaled)
+            .and_then(|seconds| i64::try_from(seconds).ok())
+            .ok_or(StreamError::Overflow)?;
+        require!(added_seconds > 0, StreamError::InvalidTopUpAmount);
+        self.end_time = self.end_time.checked_add(added_seconds)
+            .ok_or(StreamError::Overflow)?;
+        self.total_deposited = self.total_deposited.checked_add(amount)
+            .ok_or(StreamError::Overflow)?;
+        Ok(added_seconds)
+    }
+
+    /// Portion of `total_earned` owed to split `index`. The last split
+    /// absorbs rounding so the shares always add up to `total_earned`.
+    pub fn split_earned(&self, total_earned: u64, index: usize) -> Result<u64> {
+        let share = |split: &StreamSplit| -> u64 {
+            (total_earned as u128 * split.share_bps as u128 / BPS_DENOMINATOR as u128) as u64
+        };
+        if index + 1 < self.splits.len() {
+            return Ok(share(&self.splits[index]));
+        }
+        let others: u64 = self.splits[..index].iter().map(share).sum();
+        Ok(total_earned.checked_sub(others)
+            .ok_or(StreamError::Overflow)?)
+    }
+}
+
+// === Events ===
+
+#[event]
+pub struct StreamCreated {
+    pub stream: Pubkey,
+    pub sender: Pubkey,
+    pub recipient: Pubkey, // default for split streams
+    pub stream_id: u64,
+    pub mint: Option<Pubkey>, // None for SOL streams
+    pub total_deposited: u64,
+    pub rate_scaled: u128,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub cliff_time: Option<i64>,
+    pub unlock_amount: u64,
+    pub splits: Vec<SplitShare>,
+}
+
+#[event]
+pub struct StreamWithdrawn {
+    pub stream: Pubkey,
+    pub recipient: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub total_withdrawn: u64,
+}
+
+#[event]
+pub struct StreamCancelled {
+    pub stream: Pubkey,
+    pub sender: Pubkey,
+    pub paid_to_recipients: u64,
+    pub refunded: u64,
+    pub closed: bool,
+}
+
+#[event]
+pub struct StreamClosed {
+    pub stream: Pubkey,
+    pub sender: Pubkey,
+    pub swept: u64,
+}
+
+#[event]
+pub struct StreamRecipientTransferred {
+    pub stream: Pubkey,
+    pub previous_recipient: Pubkey,
+    pub new_recipient: Pubkey,
+}
+
+#[event]
+pub struct StreamPaused {
+    pub stream: Pubkey,
+    pub paused_at: i64,
+}
+
+#[event]
+pub struct StreamResumed {
+    pub stream: Pubkey,
+    pub paused_for: i64,
+    pub end_time: i64,
+}
+
+#[event]
+pub struct StreamToppedUp {
+    pub stream: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub end_time: i64,
+    pub total_deposited: u64,
 }
 
 // === Errors ===
@@ -257,4 +1328,168 @@ pub enum StreamError {
     NothingToWithdraw,
     #[msg("Arithmetic overflow")]
     Overflow,
+    #[msg("Token streams require the mint, token accounts and token program")]
+    MissingTokenAccounts,
+    #[msg("Mint does not match the stream")]
+    MintMismatch,
+    #[msg("Stream is paused")]
+    StreamPaused,
+    #[msg("Stream is not paused")]
+    StreamNotPaused,
+    #[msg("Stream has already ended")]
+    StreamEnded,
+    #[msg("Top-up amount must cover at least one second of streaming")]
+    InvalidTopUpAmount,
+    #[msg("Cliff must fall between start time and end time")]
+    InvalidCliff,
+    #[msg("Stream must be cancelled or fully withdrawn before closing")]
+    StreamNotFinished,
+    #[msg("Split shares must be unique, non-zero and sum to 10000 bps")]
+    InvalidSplits,
+    #[msg("Recipient accounts missing or out of order")]
+    MissingRecipientAccounts,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn total_amount_stream(total: u64, start_time: i64, end_time: i64) -> PaymentStream {
+        let rate_scaled = total as u128 * RATE_SCALE / (end_time - start_time) as u128;
+        PaymentStream {
+            sender: Pubkey::new_unique(),
+            recipient: Pubkey::new_unique(),
+            stream_id: 0,
+            amount_per_second: (rate_scaled / RATE_SCALE) as u64,
+            start_time,
+            end_time,
+            total_deposited: total,
+            total_withdrawn: 0,
+            is_cancelled: false,
+            vault_bump: 255,
+            created_at: start_time,
+            mint: None,
+            is_paused: false,
+            paused_at: 0,
+            total_paused_duration: 0,
+            cliff_time: None,
+            unlock_amount: 0,
+            rate_scaled,
+            splits: vec![],
+        }
+    }
+
+    #[test]
+    fn total_amount_stream_pays_exact_deposit_across_pause_and_top_up() {
+        // 1_000_003 over 7s leaves a fixed-point remainder every second
+        let mut stream = total_amount_stream(1_000_003, 1_000, 1_007);
+        let mut earned = Vec::new();
+
+        for now in 1_000..1_003 {
+            earned.push(stream.total_earned(now).unwrap());
+        }
+        stream.record_pause(1_003);
+        let at_pause = stream.total_earned(1_003).unwrap();
+        assert_eq!(stream.total_earned(1_009).unwrap(), at_pause);
+        assert_eq!(stream.record_resume(1_010).unwrap(), 7);
+        assert_eq!(stream.end_time, 1_014);
+
+        stream.record_top_up(333_334).unwrap();
+        assert_eq!(stream.total_deposited, 1_333_337);
+        for now in 1_010..stream.end_time + 3 {
+            earned.push(stream.total_earned(now).unwrap());
+        }
+
+        assert!(earned.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(earned.iter().all(|&amount| amount <= stream.total_deposited));
+        assert!(stream.total_earned(stream.end_time - 1).unwrap() < 1_333_337);
+        assert_eq!(*earned.last().unwrap(), 1_333_337);
+        assert_eq!(stream.total_earned(stream.end_time).unwrap(), 1_333_337);
+    }
+
+    #[test]
+    fn nothing_vests_before_start_or_cliff() {
+        let mut stream = total_amount_stream(1_000, 100, 110);
+        stream.unlock_amount = 50;
+        stream.total_deposited = 1_050;
+        stream.cliff_time = Some(105);
+
+        assert_eq!(stream.total_earned(99).unwrap(), 0);
+        assert_eq!(stream.total_earned(100).unwrap(), 0);
+        assert_eq!(stream.total_earned(104).unwrap(), 0);
+        // At the cliff the unlock and everything accrued since start vest at once
+        assert_eq!(stream.total_earned(105).unwrap(), 550);
+        assert_eq!(stream.total_earned(110).unwrap(), 1_050);
+        assert_eq!(stream.total_earned(1_000).unwrap(), 1_050);
+    }
+
+    #[test]
+    fn unlock_vests_at_start_without_cliff() {
+        let mut stream = total_amount_stream(1_000, 100, 110);
+        stream.unlock_amount = 50;
+        stream.total_deposited = 1_050;
+
+        assert_eq!(stream.total_earned(99).unwrap(), 0);
+        assert_eq!(stream.total_earned(100).unwrap(), 50);
+        assert_eq!(stream.total_earned(101).unwrap(), 150);
+    }
+
+    #[test]
+    fn pause_freezes_accrual_and_extends_end() {
+        let mut stream = total_amount_stream(1_000, 100, 110);
+        stream.record_pause(103);
+        assert_eq!(stream.total_earned(103).unwrap(), 300);
+        assert_eq!(stream.total_earned(108).unwrap(), 300);
+        assert_eq!(stream.total_earned(200).unwrap(), 300);
+
+        assert_eq!(stream.record_resume(108).unwrap(), 5);
+        assert_eq!(stream.end_time, 115);
+        assert_eq!(stream.total_earned(109).unwrap(), 400);
+        assert_eq!(stream.total_earned(114).unwrap(), 900);
+        assert_eq!(stream.total_earned(115).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn pause_before_start_does_not_count() {
+        let mut stream = total_amount_stream(1_000, 100, 110);
+        stream.record_pause(90);
+        assert_eq!(stream.record_resume(95).unwrap(), 0);
+        assert_eq!(stream.end_time, 110);
+        assert_eq!(stream.total_earned(105).unwrap(), 500);
+    }
+
+    #[test]
+    fn fixed_point_remainder_is_paid_at_end() {
+        // 10 over 3s streams 3.33.. per second
+        let stream = total_amount_stream(10, 100, 103);
+        assert_eq!(stream.rate_scaled, 3_333_333_333);
+        assert_eq!(stream.total_earned(101).unwrap(), 3);
+        assert_eq!(stream.total_earned(102).unwrap(), 6);
+        assert_eq!(stream.total_earned(103).unwrap(), 10);
+    }
+
+    #[test]
+    fn split_shares_add_up_to_total_earned() {
+        let mut stream = total_amount_stream(10, 100, 103);
+        stream.splits = [3_333, 3_333, 3_334]
+            .into_iter()
+            .map(|share_bps| StreamSplit {
+                recipient: Pubkey::new_unique(),
+                share_bps,
+                withdrawn: 0,
+            })
+            .collect();
+
+        for total in [0, 1, 10, 9_999, u64::MAX] {
+            let shares: Vec<u64> = (0..3)
+                .map(|index| stream.split_earned(total, index).unwrap())
+                .collect();
+            assert_eq!(shares.iter().map(|&share| share as u128).sum::<u128>(), total as u128);
+        }
+        // The last split absorbs rounding
+        assert_eq!(stream.split_earned(10, 0).unwrap(), 3);
+        assert_eq!(stream.split_earned(10, 1).unwrap(), 3);
+        assert_eq!(stream.split_earned(10, 2).unwrap(), 4);
+        assert_eq!(stream.split_earned(1, 2).unwrap(), 1);
+    }
 }
@@ -1,4 +1,10 @@
+#![allow(clippy::too_many_arguments)]
+
 use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenInterface};
+use rally_squad::cpi::accounts::{ExecuteProposalTokenWithdrawal, ExecuteProposalWithdrawal};
+use rally_squad::program::RallySquad;
+use rally_squad::{ProposalAction, Role, Squad};
 
 declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnU");
 
@@ -9,6 +15,12 @@ pub mod rally_vote {
     use super::*;
 
     /// Create a proposal for a squad spending decision.
+    /// Only members with at least `Role::Member` (not viewers) can propose.
+    /// Those members are snapshotted as the eligible voter set, so later
+    /// membership changes cannot swing an open vote.
+    /// `action` decides what execution does; see `rally_squad::ProposalAction`.
+    /// `params_hash` commits to action terms beyond amount and recipient
+    /// (`rally_squad::SquadStreamParams::hash` for streams); zero otherwise.
     pub fn create_proposal(
         ctx: Context<CreateProposal>,
         proposal_id: u64,
@@ -17,12 +29,28 @@ pub mod rally_vote {
         amount: u64,
         recipient: Pubkey,
         voting_deadline: i64,
+        action: ProposalAction,
+        params_hash: [u8; 32],
     ) -> Result<()> {
         let now = Clock::get()?.unix_timestamp;
         require!(title.len() <= 64, VoteError::TitleTooLong);
         require!(description.len() <= 256, VoteError::DescriptionTooLong);
         require!(voting_deadline > now, VoteError::DeadlineInPast);
-        require!(amount > 0, VoteError::InvalidAmount);
+        require!(
+            amount > 0
+                || matches!(
+                    action,
+                    ProposalAction::CancelStream
+                        | ProposalAction::TransferAuthority
+                        | ProposalAction::PauseStream
+                        | ProposalAction::ResumeStream
+                ),
+            VoteError::InvalidAmount
+        );
+        require!(
+            ctx.accounts.squad.has_role(&ctx.accounts.proposer.key(), Role::Member),
+            VoteError::NotSquadMember
+        );
 
         let proposal = &mut ctx.accounts.proposal;
         proposal.squad = ctx.accounts.squad.key();
@@ -38,7 +66,21 @@ pub mod rally_vote {
         proposal.voting_deadline = voting_deadline;
         proposal.is_executed = false;
         proposal.created_at = now;
+        proposal.eligible_voters = ctx.accounts.squad.voters();
+        proposal.action = action;
+        proposal.params_hash = params_hash;
 
+        emit!(ProposalCreated {
+            proposal: proposal.key(),
+            squad: proposal.squad,
+            proposer: proposal.proposer,
+            proposal_id,
+            amount,
+            recipient,
+            voting_deadline,
+            action,
+            params_hash,
+        });
         msg!("Proposal '{}' created for {} lamports", proposal.title, amount);
         Ok(())
     }
@@ -49,6 +91,10 @@ pub mod rally_vote {
         let proposal = &mut ctx.accounts.proposal;
         let voter = ctx.accounts.voter.key();
 
+        require!(
+            proposal.eligible_voters.contains(&voter),
+            VoteError::NotSquadMember
+        );
         require!(now <= proposal.voting_deadline, VoteError::VotingClosed);
         require!(!proposal.is_executed, VoteError::AlreadyExecuted);
         require!(
@@ -66,6 +112,13 @@ pub mod rally_vote {
                 .ok_or(VoteError::Overflow)?;
         }
 
+        emit!(VoteCast {
+            proposal: proposal.key(),
+            voter,
+            vote_yes,
+            yes_votes: proposal.yes_votes,
+            no_votes: proposal.no_votes,
+        });
         msg!(
             "Vote cast: {} by {}. Tally: {} yes, {} no",
             if vote_yes { "YES" } else { "NO" },
@@ -77,19 +130,19 @@ pub mod rally_vote {
     }
 
     /// Execute a proposal if it has passed.
-    /// A proposal passes when: deadline reached + yes > no + yes >= (total_members/2 + 1)
-    pub fn execute_proposal(
-        ctx: Context<ExecuteProposal>,
-        total_squad_members: u8,
-    ) -> Result<()> {
+    /// A proposal passes when: deadline reached + yes > no + yes >= (eligible voters/2 + 1)
+    /// Transfer proposals are paid out by a CPI into rally-squad signed by the
+    /// proposal PDA, so they are never marked executed without funds moving.
+    /// Other actions are consumed later by the matching rally-squad instruction.
+    pub fn execute_proposal(ctx: Context<ExecuteProposal>) -> Result<()> {
         let now = Clock::get()?.unix_timestamp;
         let proposal = &mut ctx.accounts.proposal;
 
         require!(!proposal.is_executed, VoteError::AlreadyExecuted);
         require!(now >= proposal.voting_deadline, VoteError::VotingStillOpen);
 
-        // Check quorum: yes votes must be > 50% of total members
-        let quorum = (total_squad_members as u32 / 2) + 1;
+        // Check quorum: yes votes must be > 50% of members at proposal creation
+        let quorum = (proposal.eligible_voters.len() as u32 / 2) + 1;
         require!(
             proposal.yes_votes >= quorum,
             VoteError::QuorumNotReached
@@ -101,6 +154,31 @@ pub mod rally_vote {
 
         proposal.is_executed = true;
 
+        let squad_key = proposal.squad;
+        let proposal_id = proposal.proposal_id.to_le_bytes();
+        let proposal_seeds = &[
+            b"proposal",
+            squad_key.as_ref(),
+            proposal_id.as_ref(),
+            &[ctx.bumps.proposal],
+        ];
+        let signer_seeds = &[&proposal_seeds[..]];
+
+        // Transfers pay out now; other actions are consumed later by rally-squad
+        if ctx.accounts.proposal.action == ProposalAction::Transfer {
+            pay_out(ctx.accounts, signer_seeds)?;
+        }
+
+        let proposal = &ctx.accounts.proposal;
+        emit!(ProposalExecuted {
+            proposal: proposal.key(),
+            squad: proposal.squad,
+            action: proposal.action,
+            amount: proposal.amount,
+            recipient: proposal.recipient,
+            yes_votes: proposal.yes_votes,
+            no_votes: proposal.no_votes,
+        });
         msg!(
             "Proposal '{}' executed! {} yes vs {} no (quorum: {})",
             proposal.title,
@@ -112,6 +190,65 @@ pub mod rally_vote {
     }
 }
 
+/// Pay a transfer proposal out of the squad vault via CPI into rally-squad,
+/// signing as the proposal PDA.
+fn pay_out(accounts: &ExecuteProposal, signer_seeds: &[&[&[u8]]]) -> Result<()> {
+    let (Some(vault), Some(consumed_proposal)) = (
+        accounts.vault.as_ref(),
+        accounts.consumed_proposal.as_ref(),
+    ) else {
+        return err!(VoteError::MissingTransferAccounts);
+    };
+
+    if accounts.squad.mint.is_some() {
+        let (
+            Some(mint),
+            Some(vault_token_account),
+            Some(recipient_token_account),
+            Some(token_program),
+        ) = (
+            accounts.mint.as_ref(),
+            accounts.vault_token_account.as_ref(),
+            accounts.recipient_token_account.as_ref(),
+            accounts.token_program.as_ref(),
+        ) else {
+            return err!(VoteError::MissingTokenAccounts);
+        };
+
+        rally_squad::cpi::execute_proposal_token_withdrawal(CpiContext::new_with_signer(
+            accounts.rally_squad_program.to_account_info(),
+            ExecuteProposalTokenWithdrawal {
+                squad: accounts.squad.to_account_info(),
+                mint: mint.to_account_info(),
+                vault: vault.to_account_info(),
+                vault_token_account: vault_token_account.to_account_info(),
+                proposal: accounts.proposal.to_account_info(),
+                consumed_proposal: consumed_proposal.to_account_info(),
+                recipient_token_account: recipient_token_account.to_account_info(),
+                payer: accounts.executor.to_account_info(),
+                token_program: token_program.to_account_info(),
+                system_program: accounts.system_program.to_account_info(),
+            },
+            signer_seeds,
+        ))?;
+    } else {
+        rally_squad::cpi::execute_proposal_withdrawal(CpiContext::new_with_signer(
+            accounts.rally_squad_program.to_account_info(),
+            ExecuteProposalWithdrawal {
+                squad: accounts.squad.to_account_info(),
+                vault: vault.to_account_info(),
+                proposal: accounts.proposal.to_account_info(),
+                consumed_proposal: consumed_proposal.to_account_info(),
+                recipient: accounts.recipient.to_account_info(),
+                payer: accounts.executor.to_account_info(),
+                system_program: accounts.system_program.to_account_info(),
+            },
+            signer_seeds,
+        ))?;
+    }
+    Ok(())
+}
+
 // === Accounts ===
 
 #[derive(Accounts)]
@@ -120,14 +257,14 @@ pub struct CreateProposal<'info> {
     #[account(
         init,
         payer = proposer,
-        space = Proposal::SPACE,
+        space = Proposal::space(squad.members.len()),
         seeds = [b"proposal", squad.key().as_ref(), proposal_id.to_le_bytes().as_ref()],
         bump
     )]
     pub proposal: Account<'info, Proposal>,
 
-    /// CHECK: The squad this proposal belongs to
-    pub squad: UncheckedAccount<'info>,
+    /// The squad this proposal belongs to
+    pub squad: Account<'info, Squad>,
 
     #[account(mut)]
     pub proposer: Signer<'info>,
@@ -145,10 +282,48 @@ pub struct CastVote<'info> {
 
 #[derive(Accounts)]
 pub struct ExecuteProposal<'info> {
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.squad.as_ref(), proposal.proposal_id.to_le_bytes().as_ref()],
+        bump
+    )]
     pub proposal: Account<'info, Proposal>,
 
+    /// The squad this proposal belongs to; rally-squad updates its totals on payout
+    #[account(mut, address = proposal.squad)]
+    pub squad: Account<'info, Squad>,
+
+    /// CHECK: Transfer proposals only: squad vault PDA, validated by rally-squad
+    #[account(mut)]
+    pub vault: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Transfer proposals only: rally-squad replay guard, created by the CPI
+    #[account(mut)]
+    pub consumed_proposal: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Receives the proposal amount
+    #[account(mut, address = proposal.recipient)]
+    pub recipient: UncheckedAccount<'info>,
+
+    #[account(mut)]
     pub executor: Signer<'info>,
+
+    /// Token squads only: the squad's mint
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: Token squads only: vault token account, validated by rally-squad
+    #[account(mut)]
+    pub vault_token_account: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Token squads only: recipient's token account, validated by rally-squad
+    #[account(mut)]
+    pub recipient_token_account: Option<UncheckedAccount<'info>>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+
+    pub rally_squad_program: Program<'info, RallySquad>,
+
+    pub system_program: Program<'info, System>,
 }
 
 // === State ===
@@ -164,14 +339,18 @@ pub struct Proposal {
     pub recipient: Pubkey,        // 32
     pub yes_votes: u32,           // 4
     pub no_votes: u32,            // 4
-    pub voters: Vec<Pubkey>,      // 4 + (32 * 10)
+    pub voters: Vec<Pubkey>,      // 4 + (32 * eligible)
     pub voting_deadline: i64,     // 8
     pub is_executed: bool,        // 1
     pub created_at: i64,          // 8
+    pub eligible_voters: Vec<Pubkey>, // 4 + (32 * eligible)
+    pub action: ProposalAction,   // 1
+    pub params_hash: [u8; 32],    // 32
 }
 
 impl Proposal {
-    pub const SPACE: usize = 8 +   // discriminator
+    pub fn space(num_eligible: usize) -> usize {
+        8 +     // discriminator
         32 +    // squad
         32 +    // proposer
         8 +     // proposal_id
@@ -181,11 +360,50 @@ impl Proposal {
         32 +    // recipient
         4 +     // yes_votes
         4 +     // no_votes
-        (4 + 32 * 10) + // voters (max 10)
+        (4 + 32 * num_eligible) + // voters (at most one per eligible voter)
         8 +     // voting_deadline
         1 +     // is_executed
         8 +     // created_at
-        64;     // padding
+        (4 + 32 * num_eligible) + // eligible_voters
+        1 +     // action
+        32 +    // params_hash
+        64      // padding
+    }
+}
+
+// === Events ===
+
+#[event]
+pub struct ProposalCreated {
+    pub proposal: Pubkey,
+    pub squad: Pubkey,
+    pub proposer: Pubkey,
+    pub proposal_id: u64,
+    pub amount: u64,
+    pub recipient: Pubkey,
+    pub voting_deadline: i64,
+    pub action: ProposalAction,
+    pub params_hash: [u8; 32],
+}
+
+#[event]
+pub struct VoteCast {
+    pub proposal: Pubkey,
+    pub voter: Pubkey,
+    pub vote_yes: bool,
+    pub yes_votes: u32,
+    pub no_votes: u32,
+}
+
+#[event]
+pub struct ProposalExecuted {
+    pub proposal: Pubkey,
+    pub squad: Pubkey,
+    pub action: ProposalAction,
+    pub amount: u64,
+    pub recipient: Pubkey,
+    pub yes_votes: u32,
+    pub no_votes: u32,
 }
 
 // === Errors ===
@@ -214,4 +432,10 @@ pub enum VoteError {
     ProposalRejected,
     #[msg("Arithmetic overflow")]
     Overflow,
+    #[msg("Signer is not a voting member of this squad")]
+    NotSquadMember,
+    #[msg("Token squad proposals require the mint, token accounts and token program")]
+    MissingTokenAccounts,
+    #[msg("Transfer proposals require the squad vault and replay guard accounts")]
+    MissingTransferAccounts,
 }
@@ -0,0 +1,316 @@
+// Shared setup for the rally-squad and rally-vote integration tests.
+import * as anchor from "@coral-xyz/anchor";
+import { AnchorError, BN, Program } from "@coral-xyz/anchor";
+import { Connection, Keypair, LAMPORTS_PER_SOL, PublicKey, SystemProgram } from "@solana/web3.js";
+import { expect } from "chai";
+import { RallySquad } from "../target/types/rally_squad";
+import { RallyVote } from "../target/types/rally_vote";
+
+export type Role = { viewer: {} } | { member: {} } | { treasurer: {} } | { admin: {} };
+
+export const ROLE = {
+  viewer: { viewer: {} } as Role,
+  member: { member: {} } as Role,
+  treasurer: { treasurer: {} } as Role,
+  admin: { admin: {} } as Role,
+};
+
+export const NO_PARAMS = Array(32).fill(0);
+
+export const pda = (programId: PublicKey, seeds: (Buffer | Uint8Array)[]) =>
+  PublicKey.findProgramAddressSync(seeds, programId)[0];
+
+export const u64 = (value: BN | number) => new BN(value).toArrayLike(Buffer, "le", 8);
+
+export const sleep = (ms: number) => new Promise((resolve) => setTimeout(resolve, ms));
+
+/** Expect `promise` to fail with the Anchor error `code`. */
+export const expectError = async (promise: Promise<unknown>, code: string) => {
+  let error: unknown;
+  try {
+    await promise;
+  } catch (err) {
+    error = err;
+  }
+  expect(error, `expected ${code}`).to.be.instanceOf(AnchorError);
+  expect((error as AnchorError).error.errorCode.code).to.equal(code);
+};
+
+/** Expect `promise` to fail for any reason, e.g. a system program error. */
+export const expectFailure = async (promise: Promise<unknown>) => {
+  let failed = false;
+  try {
+    await promise;
+  } catch {
+    failed = true;
+  }
+  expect(failed, "expected the transaction to fail").to.equal(true);
+};
+
+export const fundedKeypair = async (connection: Connection, sol = 10) => {
+  const keypair = Keypair.generate();
+  await connection.confirmTransaction(
+    await connection.requestAirdrop(keypair.publicKey, sol * LAMPORTS_PER_SOL),
+    "confirmed"
+  );
+  return keypair;
+};
+
+/** Unix time of the latest confirmed block, i.e. what the programs see as `Clock`. */
+export const chainTime = async (connection: Connection) => {
+  const time = await connection.getBlockTime(await connection.getSlot("confirmed"));
+  if (time === null) {
+    throw new Error("block time unavailable");
+  }
+  return time;
+};
+
+/** Wait until the cluster clock has passed `unixTime`. */
+export const waitUntil = async (connection: Connection, unixTime: number) => {
+  while ((await chainTime(connection)) <= unixTime) {
+    await sleep(500);
+  }
+};
+
+export const workspace = () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+  return {
+    provider,
+    connection: provider.connection,
+    authority: provider.wallet.publicKey,
+    squadProgram: anchor.workspace.RallySquad as Program<RallySquad>,
+    voteProgram: anchor.workspace.RallyVote as Program<RallyVote>,
+  };
+};
+
+export type SquadAccounts = {
+  squad: PublicKey;
+  vault: PublicKey;
+};
+
+/** Create a SOL squad owned by the provider wallet. */
+export const createSquad = async (
+  program: Program<RallySquad>,
+  authority: PublicKey,
+  spendThreshold: number
+): Promise<SquadAccounts> => {
+  const squadCounter = pda(program.programId, [Buffer.from("squad_counter"), authority.toBuffer()]);
+  const counter = await program.account.squadCounter.fetchNullable(squadCounter);
+  const squadId = counter ? counter.nextSquadId : new BN(0);
+  const squad = pda(program.programId, [Buffer.from("squad"), authority.toBuffer(), u64(squadId)]);
+  const vault = pda(program.programId, [Buffer.from("vault"), squad.toBuffer()]);
+
+  await program.methods
+    .initializeSquad("Test squad", new BN(spendThreshold))
+    .accountsPartial({
+      squadCounter,
+      squad,
+      vault,
+      authority,
+      systemProgram: SystemProgram.programId,
+    })
+    .rpc();
+  return { squad, vault };
+};
+
+export const invitePda = (program: Program<RallySquad>, squad: PublicKey, invitee: PublicKey) =>
+  pda(program.programId, [Buffer.from("invite"), squad.toBuffer(), invitee.toBuffer()]);
+
+export const createInvite = (
+  program: Program<RallySquad>,
+  squad: PublicKey,
+  invitee: PublicKey,
+  role: Role,
+  options: { admin?: Keypair; expiresAt?: number } = {}
+) =>
+  program.methods
+    .createInvite(invitee, role, options.expiresAt === undefined ? null : new BN(options.expiresAt))
+    .accountsPartial({
+      squad,
+      invite: invitePda(program, squad, invitee),
+      admin: options.admin?.publicKey ?? program.provider.publicKey,
+      systemProgram: SystemProgram.programId,
+    })
+    .signers(options.admin ? [options.admin] : [])
+    .rpc();
+
+export const acceptInvite = (
+  program: Program<RallySquad>,
+  squad: PublicKey,
+  invitee: Keypair,
+  inviter: PublicKey
+) =>
+  program.methods
+    .acceptInvite()
+    .accountsPartial({
+      squad,
+      invite: invitePda(program, squad, invitee.publicKey),
+      invitee: invitee.publicKey,
+      inviter,
+      systemProgram: SystemProgram.programId,
+    })
+    .signers([invitee])
+    .rpc();
+
+/** Invite and admit `member` with `role`, inviting as the squad authority. */
+export const addMember = async (
+  program: Program<RallySquad>,
+  squad: PublicKey,
+  member: Keypair,
+  role: Role
+) => {
+  await createInvite(program, squad, member.publicKey, role);
+  await acceptInvite(program, squad, member, program.provider.publicKey!);
+};
+
+export const memberStatePda = (program: Program<RallySquad>, squad: PublicKey, member: PublicKey) =>
+  pda(program.programId, [Buffer.from("member"), squad.toBuffer(), member.toBuffer()]);
+
+export const deposit = (
+  program: Program<RallySquad>,
+  { squad, vault }: SquadAccounts,
+  amount: number
+) => {
+  const depositor = program.provider.publicKey!;
+  return program.methods
+    .deposit(new BN(amount))
+    .accountsPartial({
+      squad,
+      vault,
+      depositor,
+      memberState: memberStatePda(program, squad, depositor),
+      systemProgram: SystemProgram.programId,
+    })
+    .rpc();
+};
+
+export const withdraw = (
+  program: Program<RallySquad>,
+  { squad, vault }: SquadAccounts,
+  amount: number,
+  recipient: PublicKey,
+  withdrawer?: Keypair
+) => {
+  const signer = withdrawer?.publicKey ?? program.provider.publicKey!;
+  return program.methods
+    .withdraw(new BN(amount))
+    .accountsPartial({
+      squad,
+      vault,
+      withdrawer: signer,
+      memberState: memberStatePda(program, squad, signer),
+      recipient,
+      systemProgram: SystemProgram.programId,
+    })
+    .signers(withdrawer ? [withdrawer] : [])
+    .rpc();
+};
+
+export type ProposalAction =
+  | { transfer: {} }
+  | { stream: {} }
+  | { cancelStream: {} }
+  | { transferAuthority: {} }
+  | { pauseStream: {} }
+  | { resumeStream: {} };
+
+export const proposalPda = (program: Program<RallyVote>, squad: PublicKey, proposalId: number) =>
+  pda(program.programId, [Buffer.from("proposal"), squad.toBuffer(), u64(proposalId)]);
+
+export const consumedPda = (program: Program<RallySquad>, proposal: PublicKey) =>
+  pda(program.programId, [Buffer.from("consumed"), proposal.toBuffer()]);
+
+/** Create proposal `proposalId`, open for `votingSecs` from now. Returns its address and deadline. */
+export const createProposal = async (
+  program: Program<RallyVote>,
+  squad: PublicKey,
+  proposalId: number,
+  action: ProposalAction,
+  amount: number,
+  recipient: PublicKey,
+  options: { proposer?: Keypair; votingSecs?: number } = {}
+) => {
+  const deadline = (await chainTime(program.provider.connection)) + (options.votingSecs ?? 5);
+  const proposal = proposalPda(program, squad, proposalId);
+  await program.methods
+    .createProposal(
+      new BN(proposalId),
+      `Proposal ${proposalId}`,
+      "",
+      new BN(amount),
+      recipient,
+      new BN(deadline),
+      action,
+      NO_PARAMS
+    )
+    .accountsPartial({
+      proposal,
+      squad,
+      proposer: options.proposer?.publicKey ?? program.provider.publicKey,
+      systemProgram: SystemProgram.programId,
+    })
+    .signers(options.proposer ? [options.proposer] : [])
+    .rpc();
+  return { proposal, deadline };
+};
+
+export const castVote = (
+  program: Program<RallyVote>,
+  proposal: PublicKey,
+  voteYes: boolean,
+  voter?: Keypair
+) =>
+  program.methods
+    .castVote(voteYes)
+    .accountsPartial({
+      proposal,
+      voter: voter?.publicKey ?? program.provider.publicKey,
+    })
+    .signers(voter ? [voter] : [])
+    .rpc();
+
+/** Execute a proposal; SOL transfer proposals pay out from `vault`. */
+export const executeProposal = (
+  voteProgram: Program<RallyVote>,
+  squadProgram: Program<RallySquad>,
+  { squad, vault }: SquadAccounts,
+  proposal: PublicKey,
+  recipient: PublicKey,
+  transfer: boolean
+) =>
+  voteProgram.methods
+    .executeProposal()
+    .accountsPartial({
+      proposal,
+      squad,
+      vault: transfer ? vault : null,
+      consumedProposal: transfer ? consumedPda(squadProgram, proposal) : null,
+      recipient,
+      executor: voteProgram.provider.publicKey,
+      mint: null,
+      vaultTokenAccount: null,
+      recipientTokenAccount: null,
+      tokenProgram: null,
+      rallySquadProgram: squadProgram.programId,
+      systemProgram: SystemProgram.programId,
+    })
+    .rpc();
+
+export const executeAuthorityTransfer = (
+  program: Program<RallySquad>,
+  squad: PublicKey,
+  proposal: PublicKey,
+  member?: Keypair
+) =>
+  program.methods
+    .executeAuthorityTransfer()
+    .accountsPartial({
+      squad,
+      member: member?.publicKey ?? program.provider.publicKey,
+      proposal,
+      consumedProposal: consumedPda(program, proposal),
+      systemProgram: SystemProgram.programId,
+    })
+    .signers(member ? [member] : [])
+    .rpc();
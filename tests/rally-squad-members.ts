@@ -0,0 +1,290 @@
+// Membership, roles and authority handover on the local validator.
+// Run with `anchor test --provider.cluster localnet`.
+import { Keypair, LAMPORTS_PER_SOL, PublicKey, SystemProgram } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  ROLE,
+  Role,
+  SquadAccounts,
+  acceptInvite,
+  addMember,
+  chainTime,
+  createInvite,
+  createSquad,
+  deposit,
+  expectError,
+  fundedKeypair,
+  invitePda,
+  memberStatePda,
+  waitUntil,
+  withdraw,
+  workspace,
+} from "./helpers";
+
+const SPEND_THRESHOLD = LAMPORTS_PER_SOL;
+
+describe("rally-squad members", () => {
+  const { connection, authority, squadProgram } = workspace();
+
+  const fetchSquad = (squad: PublicKey) => squadProgram.account.squad.fetch(squad);
+
+  const roleOf = async (squad: PublicKey, member: PublicKey) =>
+    (await fetchSquad(squad)).members.find((entry) => entry.key.equals(member))?.role;
+
+  const removeMember = (accounts: SquadAccounts, member: PublicKey, admin?: Keypair) =>
+    squadProgram.methods
+      .removeMember(member, false)
+      .accountsPartial({
+        squad: accounts.squad,
+        vault: accounts.vault,
+        removedMember: member,
+        memberState: memberStatePda(squadProgram, accounts.squad, member),
+        admin: admin?.publicKey ?? authority,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers(admin ? [admin] : [])
+      .rpc();
+
+  describe("authority handover", () => {
+    let accounts: SquadAccounts;
+    let members: Keypair[];
+
+    const proposeAuthority = (newAuthority: PublicKey, signer?: Keypair) =>
+      squadProgram.methods
+        .proposeAuthority(newAuthority)
+        .accountsPartial({
+          squad: accounts.squad,
+          authority: signer?.publicKey ?? authority,
+        })
+        .signers(signer ? [signer] : [])
+        .rpc();
+
+    const acceptAuthority = (newAuthority: Keypair) =>
+      squadProgram.methods
+        .acceptAuthority()
+        .accountsPartial({
+          squad: accounts.squad,
+          newAuthority: newAuthority.publicKey,
+        })
+        .signers([newAuthority])
+        .rpc();
+
+    before(async () => {
+      accounts = await createSquad(squadProgram, authority, SPEND_THRESHOLD);
+      members = [await fundedKeypair(connection), await fundedKeypair(connection)];
+      for (const member of members) {
+        await addMember(squadProgram, accounts.squad, member, ROLE.member);
+      }
+    });
+
+    it("only lets the authority nominate a member", async () => {
+      await expectError(
+        proposeAuthority(members[1].publicKey, members[0]),
+        "ConstraintHasOne"
+      );
+      await expectError(proposeAuthority(Keypair.generate().publicKey), "NotAMember");
+      expect((await fetchSquad(accounts.squad)).pendingAuthority).to.equal(null);
+    });
+
+    it("hands over once the nominee accepts", async () => {
+      await proposeAuthority(members[0].publicKey);
+      await expectError(acceptAuthority(members[1]), "NotPendingAuthority");
+
+      await acceptAuthority(members[0]);
+      const squad = await fetchSquad(accounts.squad);
+      expect(squad.authority.toBase58()).to.equal(members[0].publicKey.toBase58());
+      expect(squad.pendingAuthority).to.equal(null);
+      expect(await roleOf(accounts.squad, members[0].publicKey)).to.deep.equal(ROLE.admin);
+    });
+
+    it("cancels the nomination of a removed member", async () => {
+      await proposeAuthority(members[1].publicKey, members[0]);
+      await removeMember(accounts, members[1].publicKey, members[0]);
+
+      expect((await fetchSquad(accounts.squad)).pendingAuthority).to.equal(null);
+      await expectError(acceptAuthority(members[1]), "NotPendingAuthority");
+    });
+  });
+
+  describe("roles", () => {
+    let accounts: SquadAccounts;
+    let member: Keypair;
+    let treasurer: Keypair;
+    let viewer: Keypair;
+
+    const setMemberRole = (target: PublicKey, role: Role, admin?: Keypair) =>
+      squadProgram.methods
+        .setMemberRole(target, role)
+        .accountsPartial({
+          squad: accounts.squad,
+          admin: admin?.publicKey ?? authority,
+        })
+        .signers(admin ? [admin] : [])
+        .rpc();
+
+    before(async () => {
+      accounts = await createSquad(squadProgram, authority, SPEND_THRESHOLD);
+      await deposit(squadProgram, accounts, 3 * LAMPORTS_PER_SOL);
+      [member, treasurer, viewer] = [
+        await fundedKeypair(connection),
+        await fundedKeypair(connection),
+        await fundedKeypair(connection),
+      ];
+      await addMember(squadProgram, accounts.squad, member, ROLE.member);
+      await addMember(squadProgram, accounts.squad, treasurer, ROLE.treasurer);
+      await addMember(squadProgram, accounts.squad, viewer, ROLE.viewer);
+    });
+
+    it("only lets treasurers and admins make free spends", async () => {
+      const recipient = Keypair.generate().publicKey;
+      for (const withdrawer of [member, viewer]) {
+        await expectError(
+          withdraw(squadProgram, accounts, LAMPORTS_PER_SOL / 10, recipient, withdrawer),
+          "InsufficientRole"
+        );
+      }
+      await expectError(
+        withdraw(
+          squadProgram,
+          accounts,
+          LAMPORTS_PER_SOL / 10,
+          recipient,
+          await fundedKeypair(connection)
+        ),
+        "NotAMember"
+      );
+
+      await withdraw(squadProgram, accounts, LAMPORTS_PER_SOL / 10, recipient, treasurer);
+      expect(await connection.getBalance(recipient)).to.equal(LAMPORTS_PER_SOL / 10);
+    });
+
+    it("only lets admins invite and assign roles", async () => {
+      await expectError(
+        createInvite(squadProgram, accounts.squad, Keypair.generate().publicKey, ROLE.member, {
+          admin: treasurer,
+        }),
+        "InsufficientRole"
+      );
+      await expectError(
+        setMemberRole(member.publicKey, ROLE.admin, treasurer),
+        "InsufficientRole"
+      );
+      await expectError(
+        removeMember(accounts, viewer.publicKey, treasurer),
+        "InsufficientRole"
+      );
+
+      await setMemberRole(member.publicKey, ROLE.treasurer);
+      expect(await roleOf(accounts.squad, member.publicKey)).to.deep.equal(ROLE.treasurer);
+    });
+
+    it("keeps the authority an admin", async () => {
+      await expectError(setMemberRole(authority, ROLE.member), "AuthorityMustBeAdmin");
+      expect(await roleOf(accounts.squad, authority)).to.deep.equal(ROLE.admin);
+    });
+  });
+
+  describe("invites", () => {
+    let accounts: SquadAccounts;
+
+    const revokeInvite = (invitee: PublicKey, inviter: PublicKey) =>
+      squadProgram.methods
+        .revokeInvite()
+        .accountsPartial({
+          squad: accounts.squad,
+          invite: invitePda(squadProgram, accounts.squad, invitee),
+          admin: authority,
+          inviter,
+        })
+        .rpc();
+
+    before(async () => {
+      accounts = await createSquad(squadProgram, authority, SPEND_THRESHOLD);
+    });
+
+    it("creates squads holding only the authority", async () => {
+      const squad = await fetchSquad(accounts.squad);
+      expect(squad.members).to.have.length(1);
+      expect(squad.members[0].key.toBase58()).to.equal(authority.toBase58());
+      expect(squad.members[0].role).to.deep.equal(ROLE.admin);
+    });
+
+    it("admits the invitee with the invited role on accept", async () => {
+      const invitee = await fundedKeypair(connection);
+      await createInvite(squadProgram, accounts.squad, invitee.publicKey, ROLE.treasurer);
+
+      // Only the invitee can accept their invite
+      const impostor = await fundedKeypair(connection);
+      await expectError(
+        squadProgram.methods
+          .acceptInvite()
+          .accountsPartial({
+            squad: accounts.squad,
+            invite: invitePda(squadProgram, accounts.squad, invitee.publicKey),
+            invitee: impostor.publicKey,
+            inviter: authority,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([impostor])
+          .rpc(),
+        "ConstraintSeeds"
+      );
+
+      await acceptInvite(squadProgram, accounts.squad, invitee, authority);
+      expect(await roleOf(accounts.squad, invitee.publicKey)).to.deep.equal(ROLE.treasurer);
+      expect(
+        await squadProgram.account.invite.fetchNullable(
+          invitePda(squadProgram, accounts.squad, invitee.publicKey)
+        )
+      ).to.equal(null);
+    });
+
+    it("rejects revoked invites", async () => {
+      const invitee = await fundedKeypair(connection);
+      await createInvite(squadProgram, accounts.squad, invitee.publicKey, ROLE.member);
+      await revokeInvite(invitee.publicKey, authority);
+
+      await expectError(
+        acceptInvite(squadProgram, accounts.squad, invitee, authority),
+        "AccountNotInitialized"
+      );
+      expect(await roleOf(accounts.squad, invitee.publicKey)).to.equal(undefined);
+    });
+
+    it("rejects expired invites", async () => {
+      const invitee = await fundedKeypair(connection);
+      const expiresAt = (await chainTime(connection)) + 5;
+      await createInvite(squadProgram, accounts.squad, invitee.publicKey, ROLE.member, {
+        expiresAt,
+      });
+      await waitUntil(connection, expiresAt);
+
+      await expectError(
+        acceptInvite(squadProgram, accounts.squad, invitee, authority),
+        "InviteExpired"
+      );
+      expect(await roleOf(accounts.squad, invitee.publicKey)).to.equal(undefined);
+    });
+
+    it("voids invites from admins who have since been demoted", async () => {
+      const admin = await fundedKeypair(connection);
+      await addMember(squadProgram, accounts.squad, admin, ROLE.admin);
+      const invitee = await fundedKeypair(connection);
+      await createInvite(squadProgram, accounts.squad, invitee.publicKey, ROLE.admin, { admin });
+
+      await squadProgram.methods
+        .setMemberRole(admin.publicKey, ROLE.member)
+        .accountsPartial({ squad: accounts.squad, admin: authority })
+        .rpc();
+
+      await expectError(
+        acceptInvite(squadProgram, accounts.squad, invitee, admin.publicKey),
+        "InviterNotAdmin"
+      );
+      expect(await roleOf(accounts.squad, invitee.publicKey)).to.equal(undefined);
+
+      // Any remaining admin can clear the stale invite
+      await revokeInvite(invitee.publicKey, admin.publicKey);
+    });
+  });
+});
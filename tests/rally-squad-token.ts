@@ -0,0 +1,207 @@
+// Token squad deposit/withdraw against a mint created on the local validator.
+// Run with `anchor test --provider.cluster localnet`.
+import * as anchor from "@coral-xyz/anchor";
+import { AnchorError, BN, Program } from "@coral-xyz/anchor";
+import {
+  TOKEN_PROGRAM_ID,
+  createMint,
+  getAccount,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+} from "@solana/spl-token";
+import { Keypair, LAMPORTS_PER_SOL, PublicKey, SystemProgram } from "@solana/web3.js";
+import { expect } from "chai";
+import { RallySquad } from "../target/types/rally_squad";
+
+describe("rally-squad token vault", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.RallySquad as Program<RallySquad>;
+  const connection = provider.connection;
+  const payer = (provider.wallet as anchor.Wallet).payer;
+  const authority = provider.wallet.publicKey;
+
+  const SPEND_THRESHOLD = 1_000_000;
+
+  let mint: PublicKey;
+  let squad: PublicKey;
+  let vault: PublicKey;
+  let vaultTokenAccount: PublicKey;
+  let authorityTokenAccount: PublicKey;
+  let authorityMemberState: PublicKey;
+
+  const pda = (seeds: (Buffer | Uint8Array)[]) =>
+    PublicKey.findProgramAddressSync(seeds, program.programId)[0];
+
+  const tokenBalance = async (account: PublicKey) =>
+    Number((await getAccount(connection, account)).amount);
+
+  const expectError = async (promise: Promise<unknown>, code: string) => {
+    let error: unknown;
+    try {
+      await promise;
+    } catch (err) {
+      error = err;
+    }
+    expect(error, `expected ${code}`).to.be.instanceOf(AnchorError);
+    expect((error as AnchorError).error.errorCode.code).to.equal(code);
+  };
+
+  const withdrawTo = async (owner: PublicKey, amount: number, withdrawer?: Keypair) => {
+    const signer = withdrawer?.publicKey ?? authority;
+    const recipientTokenAccount = await getOrCreateAssociatedTokenAccount(
+      connection,
+      payer,
+      mint,
+      owner
+    );
+    return program.methods
+      .withdrawToken(new BN(amount))
+      .accountsPartial({
+        squad,
+        mint,
+        vault,
+        vaultTokenAccount,
+        withdrawer: signer,
+        memberState: pda([Buffer.from("member"), squad.toBuffer(), signer.toBuffer()]),
+        recipientTokenAccount: recipientTokenAccount.address,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers(withdrawer ? [withdrawer] : [])
+      .rpc();
+  };
+
+  before(async () => {
+    mint = await createMint(connection, payer, authority, null, 6);
+
+    const squadCounter = pda([Buffer.from("squad_counter"), authority.toBuffer()]);
+    const counter = await program.account.squadCounter.fetchNullable(squadCounter);
+    const squadId = counter ? counter.nextSquadId : new BN(0);
+
+    squad = pda([
+      Buffer.from("squad"),
+      authority.toBuffer(),
+      squadId.toArrayLike(Buffer, "le", 8),
+    ]);
+    vault = pda([Buffer.from("vault"), squad.toBuffer()]);
+    vaultTokenAccount = pda([Buffer.from("vault_token"), squad.toBuffer()]);
+    authorityMemberState = pda([Buffer.from("member"), squad.toBuffer(), authority.toBuffer()]);
+
+    await program.methods
+      .initializeTokenSquad("Token squad", new BN(SPEND_THRESHOLD))
+      .accountsPartial({
+        squadCounter,
+        squad,
+        vault,
+        mint,
+        vaultTokenAccount,
+        authority,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    authorityTokenAccount = (
+      await getOrCreateAssociatedTokenAccount(connection, payer, mint, authority)
+    ).address;
+    await mintTo(connection, payer, mint, authorityTokenAccount, payer, 10_000_000);
+  });
+
+  it("creates the squad around the mint", async () => {
+    const account = await program.account.squad.fetch(squad);
+    expect(account.mint?.toBase58()).to.equal(mint.toBase58());
+    expect(account.members).to.have.length(1);
+    expect(account.members[0].key.toBase58()).to.equal(authority.toBase58());
+    expect(await tokenBalance(vaultTokenAccount)).to.equal(0);
+  });
+
+  it("deposits tokens into the vault", async () => {
+    await program.methods
+      .depositToken(new BN(5_000_000))
+      .accountsPartial({
+        squad,
+        mint,
+        vaultTokenAccount,
+        depositorTokenAccount: authorityTokenAccount,
+        depositor: authority,
+        memberState: authorityMemberState,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    expect(await tokenBalance(vaultTokenAccount)).to.equal(5_000_000);
+    expect(await tokenBalance(authorityTokenAccount)).to.equal(5_000_000);
+    const account = await program.account.squad.fetch(squad);
+    expect(account.totalDeposited.toNumber()).to.equal(5_000_000);
+    expect(account.totalNetContributions.toNumber()).to.equal(5_000_000);
+    const memberState = await program.account.memberState.fetch(authorityMemberState);
+    expect(memberState.deposited.toNumber()).to.equal(5_000_000);
+    expect(memberState.net.toNumber()).to.equal(5_000_000);
+  });
+
+  it("rejects deposits in another mint", async () => {
+    const otherMint = await createMint(connection, payer, authority, null, 6);
+    const otherTokenAccount = (
+      await getOrCreateAssociatedTokenAccount(connection, payer, otherMint, authority)
+    ).address;
+    await mintTo(connection, payer, otherMint, otherTokenAccount, payer, 1_000);
+
+    await expectError(
+      program.methods
+        .depositToken(new BN(1_000))
+        .accountsPartial({
+          squad,
+          mint: otherMint,
+          vaultTokenAccount,
+          depositorTokenAccount: otherTokenAccount,
+          depositor: authority,
+          memberState: authorityMemberState,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc(),
+      "WrongVaultAsset"
+    );
+  });
+
+  it("lets a treasurer withdraw below the spend threshold", async () => {
+    const recipient = Keypair.generate().publicKey;
+    await withdrawTo(recipient, 400_000);
+
+    const recipientTokenAccount = await getOrCreateAssociatedTokenAccount(
+      connection,
+      payer,
+      mint,
+      recipient
+    );
+    expect(Number(recipientTokenAccount.amount)).to.equal(400_000);
+    expect(await tokenBalance(vaultTokenAccount)).to.equal(4_600_000);
+
+    const account = await program.account.squad.fetch(squad);
+    expect(account.totalWithdrawn.toNumber()).to.equal(400_000);
+    expect(account.totalDeposited.toNumber()).to.equal(5_000_000);
+    expect(account.totalNetContributions.toNumber()).to.equal(4_600_000);
+    const memberState = await program.account.memberState.fetch(authorityMemberState);
+    expect(memberState.withdrawn.toNumber()).to.equal(400_000);
+    expect(memberState.net.toNumber()).to.equal(4_600_000);
+  });
+
+  it("requires a vote above the spend threshold", async () => {
+    await expectError(withdrawTo(authority, SPEND_THRESHOLD + 1), "VoteRequired");
+    expect(await tokenBalance(vaultTokenAccount)).to.equal(4_600_000);
+  });
+
+  it("rejects withdrawals by non-members", async () => {
+    const outsider = Keypair.generate();
+    await connection.confirmTransaction(
+      await connection.requestAirdrop(outsider.publicKey, LAMPORTS_PER_SOL),
+      "confirmed"
+    );
+
+    await expectError(withdrawTo(outsider.publicKey, 1_000, outsider), "NotAMember");
+    expect(await tokenBalance(vaultTokenAccount)).to.equal(4_600_000);
+  });
+});
@@ -0,0 +1,272 @@
+// Governance paths between rally-vote and rally-squad on the local validator.
+// Run with `anchor test --provider.cluster localnet`.
+import { Keypair, LAMPORTS_PER_SOL, SystemProgram } from "@solana/web3.js";
+import { expect } from "chai";
+import {
+  ROLE,
+  SquadAccounts,
+  addMember,
+  castVote,
+  consumedPda,
+  createProposal,
+  createSquad,
+  deposit,
+  executeAuthorityTransfer,
+  executeProposal,
+  expectError,
+  expectFailure,
+  fundedKeypair,
+  waitUntil,
+  withdraw,
+  workspace,
+} from "./helpers";
+
+const SPEND_THRESHOLD = LAMPORTS_PER_SOL;
+
+describe("rally-vote governance", () => {
+  const { connection, authority, squadProgram, voteProgram } = workspace();
+
+  /** A squad of the authority plus two `Role::Member`s, holding 5 SOL. */
+  const setUpSquad = async () => {
+    const accounts = await createSquad(squadProgram, authority, SPEND_THRESHOLD);
+    const members = [await fundedKeypair(connection), await fundedKeypair(connection)];
+    for (const member of members) {
+      await addMember(squadProgram, accounts.squad, member, ROLE.member);
+    }
+    await deposit(squadProgram, accounts, 5 * LAMPORTS_PER_SOL);
+    return { accounts, members };
+  };
+
+  describe("above-threshold spends", () => {
+    let accounts: SquadAccounts;
+    let members: Keypair[];
+
+    before(async () => {
+      ({ accounts, members } = await setUpSquad());
+    });
+
+    it("rejects free spends above the spend threshold", async () => {
+      await expectError(
+        withdraw(squadProgram, accounts, SPEND_THRESHOLD + 1, Keypair.generate().publicKey),
+        "VoteRequired"
+      );
+    });
+
+    it("does not accept an executed proposal twice", async () => {
+      const newAuthority = members[0].publicKey;
+      const { proposal, deadline } = await createProposal(
+        voteProgram,
+        accounts.squad,
+        0,
+        { transferAuthority: {} },
+        0,
+        newAuthority
+      );
+      await castVote(voteProgram, proposal, true);
+      await castVote(voteProgram, proposal, true, members[0]);
+      await waitUntil(connection, deadline);
+      await executeProposal(voteProgram, squadProgram, accounts, proposal, newAuthority, false);
+
+      await executeAuthorityTransfer(squadProgram, accounts.squad, proposal);
+      const consumed = await squadProgram.account.consumedProposal.fetch(
+        consumedPda(squadProgram, proposal)
+      );
+      expect(consumed.proposal.toBase58()).to.equal(proposal.toBase58());
+
+      // The ConsumedProposal PDA already exists, so a replay can't be recorded
+      await expectFailure(executeAuthorityTransfer(squadProgram, accounts.squad, proposal, members[1]));
+    });
+  });
+
+  describe("proposal payouts", () => {
+    let accounts: SquadAccounts;
+    let members: Keypair[];
+    const recipient = Keypair.generate().publicKey;
+    const amount = 2 * LAMPORTS_PER_SOL;
+
+    before(async () => {
+      ({ accounts, members } = await setUpSquad());
+    });
+
+    it("pays an approved transfer by CPI from rally-vote", async () => {
+      const { proposal, deadline } = await createProposal(
+        voteProgram,
+        accounts.squad,
+        0,
+        { transfer: {} },
+        amount,
+        recipient
+      );
+      await castVote(voteProgram, proposal, true);
+      await castVote(voteProgram, proposal, true, members[0]);
+      await waitUntil(connection, deadline);
+
+      const vaultBefore = await connection.getBalance(accounts.vault);
+      await executeProposal(voteProgram, squadProgram, accounts, proposal, recipient, true);
+
+      expect(await connection.getBalance(recipient)).to.equal(amount);
+      expect(await connection.getBalance(accounts.vault)).to.equal(vaultBefore - amount);
+      expect((await voteProgram.account.proposal.fetch(proposal)).isExecuted).to.equal(true);
+      const squad = await squadProgram.account.squad.fetch(accounts.squad);
+      expect(squad.totalWithdrawn.toNumber()).to.equal(amount);
+      const consumed = await squadProgram.account.consumedProposal.fetch(
+        consumedPda(squadProgram, proposal)
+      );
+      expect(consumed.amount.toNumber()).to.equal(amount);
+      expect(consumed.recipient.toBase58()).to.equal(recipient.toBase58());
+
+      await expectError(
+        executeProposal(voteProgram, squadProgram, accounts, proposal, recipient, true),
+        "AlreadyExecuted"
+      );
+      expect(await connection.getBalance(recipient)).to.equal(amount);
+    });
+
+    it("rejects payouts that rally-vote did not sign for", async () => {
+      const { proposal } = await createProposal(
+        voteProgram,
+        accounts.squad,
+        1,
+        { transfer: {} },
+        amount,
+        recipient
+      );
+
+      await expectError(
+        squadProgram.methods
+          .executeProposalWithdrawal()
+          .accountsPartial({
+            squad: accounts.squad,
+            vault: accounts.vault,
+            proposal,
+            consumedProposal: consumedPda(squadProgram, proposal),
+            recipient,
+            payer: authority,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc(),
+        "ProposalNotExecuted"
+      );
+      expect(await connection.getBalance(recipient)).to.equal(amount);
+    });
+  });
+
+  describe("quorum", () => {
+    let accounts: SquadAccounts;
+    let members: Keypair[];
+
+    before(async () => {
+      ({ accounts, members } = await setUpSquad());
+    });
+
+    it("needs a majority of the squad's members, not just of the votes cast", async () => {
+      const recipient = Keypair.generate().publicKey;
+      const { proposal, deadline } = await createProposal(
+        voteProgram,
+        accounts.squad,
+        0,
+        { transfer: {} },
+        2 * LAMPORTS_PER_SOL,
+        recipient
+      );
+      // One yes and no opposition is still short of 2 of 3 members
+      await castVote(voteProgram, proposal, true);
+      await waitUntil(connection, deadline);
+
+      await expectError(
+        executeProposal(voteProgram, squadProgram, accounts, proposal, recipient, true),
+        "QuorumNotReached"
+      );
+      const account = await voteProgram.account.proposal.fetch(proposal);
+      expect(account.eligibleVoters).to.have.length(1 + members.length);
+      expect(account.isExecuted).to.equal(false);
+    });
+  });
+
+  describe("proposal access", () => {
+    let accounts: SquadAccounts;
+    let outsider: Keypair;
+    let viewer: Keypair;
+
+    before(async () => {
+      ({ accounts } = await setUpSquad());
+      outsider = await fundedKeypair(connection);
+      viewer = await fundedKeypair(connection);
+      await addMember(squadProgram, accounts.squad, viewer, ROLE.viewer);
+    });
+
+    it("rejects proposals from non-members and viewers", async () => {
+      for (const [proposalId, proposer] of [outsider, viewer].entries()) {
+        await expectError(
+          createProposal(
+            voteProgram,
+            accounts.squad,
+            proposalId,
+            { transfer: {} },
+            LAMPORTS_PER_SOL,
+            proposer.publicKey,
+            { proposer }
+          ),
+          "NotSquadMember"
+        );
+      }
+    });
+
+    it("rejects votes from non-members and viewers", async () => {
+      const { proposal } = await createProposal(
+        voteProgram,
+        accounts.squad,
+        0,
+        { transfer: {} },
+        LAMPORTS_PER_SOL,
+        outsider.publicKey,
+        { votingSecs: 60 }
+      );
+
+      for (const voter of [outsider, viewer]) {
+        await expectError(castVote(voteProgram, proposal, true, voter), "NotSquadMember");
+      }
+      const account = await voteProgram.account.proposal.fetch(proposal);
+      expect(account.yesVotes).to.equal(0);
+      expect(account.voters).to.have.length(0);
+    });
+  });
+
+  describe("eligible voter snapshot", () => {
+    let accounts: SquadAccounts;
+    let members: Keypair[];
+
+    before(async () => {
+      ({ accounts, members } = await setUpSquad());
+    });
+
+    it("freezes voters and quorum at proposal creation", async () => {
+      const newAuthority = members[0].publicKey;
+      const { proposal, deadline } = await createProposal(
+        voteProgram,
+        accounts.squad,
+        0,
+        { transferAuthority: {} },
+        0,
+        newAuthority
+      );
+
+      const lateJoiner = await fundedKeypair(connection);
+      await addMember(squadProgram, accounts.squad, lateJoiner, ROLE.member);
+      await expectError(castVote(voteProgram, proposal, true, lateJoiner), "NotSquadMember");
+
+      // 2 of the 3 snapshotted voters pass it, though the squad now has 4 members
+      await castVote(voteProgram, proposal, true);
+      await castVote(voteProgram, proposal, true, members[0]);
+      await waitUntil(connection, deadline);
+      await executeProposal(voteProgram, squadProgram, accounts, proposal, newAuthority, false);
+
+      const account = await voteProgram.account.proposal.fetch(proposal);
+      expect(account.isExecuted).to.equal(true);
+      expect(account.eligibleVoters.map((voter) => voter.toBase58())).to.have.members([
+        authority.toBase58(),
+        ...members.map((member) => member.publicKey.toBase58()),
+      ]);
+    });
+  });
+});